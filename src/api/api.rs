@@ -1,170 +1,299 @@
-use std::{env, fs};
-use image::GenericImageView;
+use std::{fmt::Display, sync::Arc, time::Instant};
 use rocket;
-use rocket::data::ByteUnit;
-use rocket::{post, Data};
-use rocket::tokio::io::AsyncReadExt;
+use rocket::data::Limits;
+use rocket::http::ContentType;
+use rocket::{post, Data, State};
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::{sync::mpsc, task};
+use crate::api::auth::ApiKey;
+use crate::api::cache::{CacheKey, Cached, IfNoneMatch, ResultCache};
+use crate::api::metrics::Metrics;
+use crate::api::docs::{MultipartUpload, RawUpload};
+use crate::api::config::{upload_limit, Conversions, Parallelism, DEFAULT_AUDIO_LIMIT, DEFAULT_IMAGE_LIMIT, DEFAULT_VIDEO_LIMIT};
+use crate::api::response::{check_dimensions, too_large, ApiError, AsciiResponse, FrameEvent};
+use crate::api::upload::{receive_upload, ConversionOptions};
+use crate::audio::audio_to_ascii::AudioOptions;
+use crate::core::convert::{convert_detected, detect_bytes, ConvertOptions};
+use crate::core::probe::MediaKind;
+use crate::image::decode::decode_image_bytes;
 use crate::prelude::{AsciiAudio, AsciiImg, AsciiVid};
 use crate::utils::utils::Verbosity;
-use xxhash_rust::xxh3::xxh3_128;
+use crate::video::{source::TempVideo, video_to_ascii::{FrameProgress, StreamOutcome}};
 
 
 // pub fn new_parallel(path: String, target_height: Option<u32>, target_width: Option<u32>, invert: bool, grayscale: bool, uniform: bool)
 
-#[post("/api/img_to_ascii?<height>&<width>&<invert>&<colored>&<uniform>", data = "<data>")]
-pub async fn api_img_to_ascii_parallel(
+/// the string between the frames of a video in the `text` of a response; `read --read-api-output` splits on it.
+pub(crate) const FRAME_SEPARATOR: &str = "\n###\n";
+
+#[utoipa::path(
+    tag = "image",
+    summary = "Convert an image to ASCII art",
+    request_body(
+        description = "the image, in any supported format, as the raw body or as the `file` part of a form, with an optional `options` part",
+        content((RawUpload = "application/octet-stream"), (MultipartUpload = "multipart/form-data")),
+    ),
+    params(ConversionOptions),
+    responses(
+        (status = 200, description = "the ASCII art", content(
+            (AsciiResponse = "application/json"),
+            (String = "text/plain"),
+            (String = "text/html"),
+            (String = "image/svg+xml"),
+        ), headers(("ETag" = String), ("X-Cache" = String, description = "`HIT` or `MISS`"))),
+        (status = 304, description = "the `If-None-Match` header already holds the result"),
+        (status = "4XX", description = "bad options, or an upload that's too big, unsupported or broken", body = ApiError, content_type = "application/problem+json"),
+        (status = "5XX", description = "the conversion failed or took too long", body = ApiError, content_type = "application/problem+json"),
+    ),
+)]
+#[post("/api/img_to_ascii?<options..>", data = "<data>")]
+pub async fn api_img_to_ascii(
+    _key: ApiKey,
+    cache: &State<ResultCache>,
+    if_none_match: IfNoneMatch,
+    limits: &Limits,
+    conversions: &State<Arc<Conversions>>,
+    metrics: &State<Arc<Metrics>>,
+    content_type: Option<&ContentType>,
     data: Data<'_>,
-    height: Option<usize>,
-    width: Option<usize>,
-    invert: bool,
-    colored: bool,
-    uniform: bool,
-) -> String {
-    let mut buffer = Vec::new();
-
+    options: ConversionOptions,
+) -> Result<Cached, ApiError> {
     // Read the raw bytes from the body of the request
-    data.open(ByteUnit::Megabyte(10)).read_to_end(&mut buffer).await.unwrap();
-
-    // Log the size of the buffer to check that image data is received
-    println!("Received image data: {} bytes", buffer.len());
-
-    // Ensure the buffer contains data
-    if buffer.is_empty() {
-        return "No image data received.".to_string();
-    }
-
-    // Try to load the image from the raw bytes
-    let image = match image::load_from_memory(&buffer) {
-        Ok(img) => img,
-        Err(e) => return format!("error: {}", e),
-    };
-
-    // Log the image dimensions
-    println!("Image loaded with dimensions: {:?}", image.dimensions());
-
-    // Process the image and return its ASCII representation
-    let ascii_image = AsciiImg::new_parallel(image, height, width, invert, !colored, uniform, Verbosity::Normal)
-        .unwrap();
+    let upload = receive_upload(data, content_type, upload_limit(limits, "image", DEFAULT_IMAGE_LIMIT), "image").await?;
+    let (buffer, format) = (upload.bytes, upload.format);
+    let options = options.or(upload.options);
+
+    check_dimensions(options.height, options.width)?;
+    let parallelism = conversions.parallelism(options.parallel, options.threads)?;
+    let (height, width) = (options.height, options.width);
+    let invert = options.invert.unwrap_or(false);
+    let colored = options.colored.unwrap_or(false);
+    let uniform = options.uniform.unwrap_or(false);
+
+    let key = CacheKey::new("image", &buffer, &[
+        ("format", format.as_ref().map(|x| x as &dyn Display)),
+        ("height", height.as_ref().map(|x| x as &dyn Display)),
+        ("width", width.as_ref().map(|x| x as &dyn Display)),
+        ("invert", Some(&invert)),
+        ("colored", Some(&colored)),
+        ("uniform", Some(&uniform)),
+    ]);
+
+    Cached::resolve(cache, conversions, metrics, parallelism, key, &if_none_match, move |parallel| {
+        // Try to load the image from the raw bytes, turned the way its EXIF orientation says; the format the client
+        // gave only counts for formats that can't be told apart by their first bytes
+        let image = decode_image_bytes(&buffer, format.as_deref())?;
+
+        // Process the image and return its ASCII representation
+        let ascii_image = if parallel {
+            AsciiImg::new_parallel(image, height, width, invert, !colored, uniform, Verbosity::Silent)?
+        } else {
+            AsciiImg::new_sequential(image, height, width, invert, !colored, uniform, Verbosity::Silent)?
+        };
+
+        Ok(AsciiResponse::image(&ascii_image))
+    }).await
+}
 
-    ascii_image.to_string()
+#[utoipa::path(
+    tag = "video",
+    summary = "Convert a video to ASCII art",
+    request_body(
+        description = "the video, in any format ffmpeg can decode, as the raw body or as the `file` part of a form, with an optional `options` part",
+        content((RawUpload = "application/octet-stream"), (MultipartUpload = "multipart/form-data")),
+    ),
+    params(ConversionOptions),
+    responses(
+        (status = 200, description = "the ASCII art of every frame, joined by `separator`", content(
+            (AsciiResponse = "application/json"),
+            (String = "text/plain"),
+            (String = "text/html"),
+        ), headers(("ETag" = String), ("X-Cache" = String, description = "`HIT` or `MISS`"))),
+        (status = 304, description = "the `If-None-Match` header already holds the result"),
+        (status = "4XX", description = "bad options, or an upload that's too big, unsupported or broken", body = ApiError, content_type = "application/problem+json"),
+        (status = "5XX", description = "the conversion failed or took too long", body = ApiError, content_type = "application/problem+json"),
+    ),
+)]
+#[post("/api/video_to_ascii?<options..>", data = "<data>")]
+pub async fn api_video_to_ascii(
+    _key: ApiKey,
+    cache: &State<ResultCache>,
+    if_none_match: IfNoneMatch,
+    limits: &Limits,
+    conversions: &State<Arc<Conversions>>,
+    metrics: &State<Arc<Metrics>>,
+    content_type: Option<&ContentType>,
+    data: Data<'_>,
+    options: ConversionOptions,
+) -> Result<Cached, ApiError> {
+    // Buffer to store video data
+    let upload = receive_upload(data, content_type, upload_limit(limits, "video", DEFAULT_VIDEO_LIMIT), "video").await?;
+    let (buffer, format) = (upload.bytes, upload.format);
+    let options = options.or(upload.options);
+
+    check_dimensions(options.height, options.width)?;
+    let parallelism = conversions.parallelism(options.parallel, options.threads)?;
+    let (height, width, nframes) = (options.height, options.width, options.nframes);
+    let invert = options.invert.unwrap_or(false);
+    let colored = options.colored.unwrap_or(false);
+    let uniform = options.uniform.unwrap_or(false);
+
+    let key = CacheKey::new("video", &buffer, &[
+        ("format", format.as_ref().map(|x| x as &dyn Display)),
+        ("height", height.as_ref().map(|x| x as &dyn Display)),
+        ("width", width.as_ref().map(|x| x as &dyn Display)),
+        ("nframes", nframes.as_ref().map(|x| x as &dyn Display)),
+        ("invert", Some(&invert)),
+        ("colored", Some(&colored)),
+        ("uniform", Some(&uniform)),
+    ]);
+
+    Cached::resolve(cache, conversions, metrics, parallelism, key, &if_none_match, move |parallel| {
+        let vid_ascii = AsciiVid::from_reader(
+            buffer.as_slice(),
+            format.as_deref(),
+            nframes,
+            height,
+            width,
+            invert,
+            !colored,
+            uniform,
+            parallel,
+            Verbosity::Silent,
+            &FrameProgress::default(),
+        );
+
+        // frames are joined with a separator in the text, the json also gets their timestamps
+        Ok(AsciiResponse::video(&vid_ascii?, FRAME_SEPARATOR))
+    }).await
 }
 
-#[post("/api/img_to_ascii?<height>&<width>&<invert>&<colored>&<uniform>", data = "<data>")]
-pub async fn api_img_to_ascii_sequential(
+/// how many converted frames can wait for a slow client before the conversion pauses.
+const STREAM_BUFFER_FRAMES: usize = 8;
+
+#[utoipa::path(
+    tag = "video",
+    summary = "Stream the frames of a video as they get converted",
+    description = "Server-sent events: a `frame` event per frame, then an `end` event with the number of frames, or an `error` event with a problem object.",
+    request_body(
+        description = "the video, in any format ffmpeg can decode, as the raw body or as the `file` part of a form, with an optional `options` part",
+        content((RawUpload = "application/octet-stream"), (MultipartUpload = "multipart/form-data")),
+    ),
+    params(ConversionOptions),
+    responses(
+        (status = 200, description = "the stream of `frame` events", body = FrameEvent, content_type = "text/event-stream"),
+        (status = "4XX", description = "bad options, or an upload that's too big", body = ApiError, content_type = "application/problem+json"),
+    ),
+)]
+#[post("/api/video_to_ascii/stream?<options..>", data = "<data>")]
+pub async fn api_video_to_ascii_stream(
+    _key: ApiKey,
+    limits: &Limits,
+    conversions: &State<Arc<Conversions>>,
+    metrics: &State<Arc<Metrics>>,
+    content_type: Option<&ContentType>,
     data: Data<'_>,
-    height: Option<usize>,
-    width: Option<usize>,
-    invert: bool,
-    colored: bool,
-    uniform: bool,
-) -> String {
-    let mut buffer = Vec::new();
+    options: ConversionOptions,
+) -> Result<EventStream![], ApiError> {
+    let upload = receive_upload(data, content_type, upload_limit(limits, "video", DEFAULT_VIDEO_LIMIT), "video").await?;
+    let options = options.or(upload.options);
 
-    // Read the raw bytes from the body of the request
-    data.open(ByteUnit::Megabyte(10)).read_to_end(&mut buffer).await.unwrap();
+    check_dimensions(options.height, options.width)?;
+    let parallelism = conversions.parallelism(options.parallel, options.threads)?;
 
-    // Log the size of the buffer to check that image data is received
-    println!("Received image data: {} bytes", buffer.len());
+    Ok(stream_video(conversions.inner().clone(), metrics.inner().clone(), parallelism, upload.bytes, upload.format, options))
+}
 
-    // Ensure the buffer contains data
-    if buffer.is_empty() {
-        return "No image data received.".to_string();
+/// ## Info
+/// converts the video on the blocking thread pool and sends every frame as a `frame` event as soon as it's ready,
+/// followed by an `end` event with the number of frames, or an `error` event with a problem JSON object.
+///
+/// ## Note
+/// if the client disconnects, the conversion stops at the next frame. Streams wait for a conversion slot like every
+/// other conversion, but never time out.
+fn stream_video(
+    conversions: Arc<Conversions>,
+    metrics: Arc<Metrics>,
+    parallelism: Parallelism,
+    buffer: Vec<u8>,
+    format: Option<String>,
+    options: ConversionOptions,
+) -> EventStream![] {
+    let (sender, mut receiver) = mpsc::channel(STREAM_BUFFER_FRAMES);
+
+    rocket::tokio::spawn(async move {
+        let Ok(permit) = conversions.permits.clone().acquire_owned().await else {
+            return;
+        };
+
+        let _ = task::spawn_blocking(move || {
+            let error_sender = sender.clone();
+            let installed = conversions.install(parallelism, || {
+                stream_frames(sender, &metrics, buffer, format, options, parallelism.parallel)
+            });
+            if let Err(e) = installed {
+                let _ = error_sender.blocking_send(Event::json(&e).event("error"));
+            }
+            drop(permit);
+        })
+        .await;
+    });
+
+    EventStream! {
+        while let Some(event) = receiver.recv().await {
+            yield event;
+        }
     }
-
-    // Try to load the image from the raw bytes
-    let image = match image::load_from_memory(&buffer) {
-        Ok(img) => img,
-        Err(e) => return format!("error: {}", e),
-    };
-
-    // Log the image dimensions
-    println!("Image loaded with dimensions: {:?}", image.dimensions());
-
-    // Process the image and return its ASCII representation
-    let ascii_image = AsciiImg::new_sequential(image, height, width, invert, !colored, uniform, Verbosity::Normal)
-        .unwrap();
-
-    ascii_image.to_string()
 }
 
-#[post("/api/video_to_ascii?<height>&<width>&<nframes>&<invert>&<colored>&<uniform>", data = "<data>")]
-pub async fn api_video_to_ascii_parallel(
-    data: Data<'_>,
-    height: Option<usize>,
-    width: Option<usize>,
-    nframes: Option<usize>,
-    invert: bool,
-    colored: bool,
-    uniform: bool,
-) -> String {
-    // Buffer to store video data
-    let mut buffer = Vec::new();
-    data.open(ByteUnit::Megabyte(100))
-        .read_to_end(&mut buffer)
-        .await
-        .unwrap();
-
-    
-
-    let hash = xxh3_128(&buffer);
-    
-    // Save the received video data to a temporary file
-    let video_path = format!("/tmp/received_video:{}.mp4", hash);
-    std::fs::write(&video_path, buffer).unwrap();
-    
-    
-    let vid_ascii = AsciiVid::new_paralleled(&video_path, nframes, height, width, invert, !colored, uniform, Verbosity::Normal);
-    
-    #[allow(unused_must_use)]
-    fs::remove_file(video_path);
-    
-    let ascii_frames = match vid_ascii {
-        Ok(ascii_video) => ascii_video.0.into_iter().map(|x| x.to_string()).collect::<Vec<_>>(),
-        Err(e) => return format!("error: {}", e)
+/// ## Info
+/// the blocking half of [`stream_video`]: converts the video and sends its events through `sender`.
+fn stream_frames(
+    sender: mpsc::Sender<Event>,
+    metrics: &Metrics,
+    buffer: Vec<u8>,
+    format: Option<String>,
+    options: ConversionOptions,
+    parallel: bool,
+) {
+    // ffmpeg decodes from a path; the temp file goes away with `video`, however the stream ends.
+    let video = match TempVideo::from_bytes(&buffer, format.as_deref()) {
+        Ok(video) => video,
+        Err(e) => {
+            let _ = sender.blocking_send(Event::json(&ApiError::from(e)).event("error"));
+            return;
+        }
     };
-
-    // Join all frames with a separator
-    ascii_frames.join("\n---\n")
-}
-
-#[post("/api/video_to_ascii?<height>&<width>&<nframes>&<invert>&<colored>&<uniform>", data = "<data>")]
-pub async fn api_video_to_ascii_sequential(
-    data: Data<'_>,
-    height: Option<usize>,
-    width: Option<usize>,
-    nframes: Option<usize>,
-    invert: bool,
-    colored: bool,
-    uniform: bool,
-) -> String {
-    // Buffer to store video data
-    let mut buffer = Vec::new();
-    data.open(ByteUnit::Megabyte(100))
-        .read_to_end(&mut buffer)
-        .await
-        .unwrap();
-
-    
-
-    let hash = xxh3_128(&buffer);
-    
-    // Save the received video data to a temporary file
-    let temp_dir = env::temp_dir();
-    let video_path = temp_dir.join(format!("received_video:{}.mp4", hash)).to_string_lossy().to_string();    
-    std::fs::write(&video_path, buffer).unwrap();
-    
-    
-    let vid_ascii = AsciiVid::new_sequential(&video_path, nframes, height, width, invert, !colored, uniform, Verbosity::Normal);
-
-    #[allow(unused_must_use)]
-    fs::remove_file(video_path);
-    let ascii_frames = match vid_ascii {
-        Ok(ascii_video) => ascii_video.0.into_iter().map(|x| x.to_string()).collect::<Vec<_>>(),
-        Err(e) => return format!("error: {}", e)
+    drop(buffer);
+
+    let (height, width, nframes) = (options.height, options.width, options.nframes);
+    let invert = options.invert.unwrap_or(false);
+    let colored = options.colored.unwrap_or(false);
+    let uniform = options.uniform.unwrap_or(false);
+
+    let start = Instant::now();
+    let send_frame = |index, timestamp, frame: AsciiImg| {
+        sender
+            .blocking_send(Event::json(&FrameEvent::new(index, timestamp, &frame)).event("frame"))
+            .is_ok()
     };
-
-    // Join all frames with a separator
-    ascii_frames.join("\n###\n")
+    let streamed = if parallel {
+        AsciiVid::stream_paralleled(video.path(), nframes, height, width, invert, !colored, uniform, Verbosity::Silent, send_frame)
+    } else {
+        AsciiVid::stream_sequential(video.path(), nframes, height, width, invert, !colored, uniform, Verbosity::Silent, send_frame)
+    };
+    drop(video);
+
+    let last = match streamed {
+        Ok(StreamOutcome::Finished(frames)) => {
+            metrics.record_conversion("video", start.elapsed(), frames);
+            Event::json(&serde_json::json!({ "frames": frames })).event("end")
+        }
+        // nobody is listening anymore, and an abandoned stream isn't a finished conversion.
+        Ok(StreamOutcome::Cancelled(_)) => return,
+        Err(e) => Event::json(&ApiError::from(e)).event("error"),
+    };
+    let _ = sender.blocking_send(last);
 }
 
 //media_type: String,
@@ -172,80 +301,178 @@ pub async fn api_video_to_ascii_sequential(
 // uniform: bool,
 // invert: bool,
 
-#[post("/api/audio_to_ascii?<height>&<invert>&<uniform>", data = "<data>")]
-pub async fn api_audio_to_ascii_parallel(
+#[utoipa::path(
+    tag = "audio",
+    summary = "Convert the waveform of an audio file to ASCII art",
+    request_body(
+        description = "the audio, in any supported format, as the raw body or as the `file` part of a form, with an optional `options` part",
+        content((RawUpload = "application/octet-stream"), (MultipartUpload = "multipart/form-data")),
+    ),
+    params(ConversionOptions),
+    responses(
+        (status = 200, description = "the ASCII art of the waveform", content(
+            (AsciiResponse = "application/json"),
+            (String = "text/plain"),
+            (String = "text/html"),
+            (String = "image/svg+xml"),
+        ), headers(("ETag" = String), ("X-Cache" = String, description = "`HIT` or `MISS`"))),
+        (status = 304, description = "the `If-None-Match` header already holds the result"),
+        (status = "4XX", description = "bad options, or an upload that's too big, unsupported or broken", body = ApiError, content_type = "application/problem+json"),
+        (status = "5XX", description = "the conversion failed or took too long", body = ApiError, content_type = "application/problem+json"),
+    ),
+)]
+#[post("/api/audio_to_ascii?<options..>", data = "<data>")]
+pub async fn api_audio_to_ascii(
+    _key: ApiKey,
+    cache: &State<ResultCache>,
+    if_none_match: IfNoneMatch,
+    limits: &Limits,
+    conversions: &State<Arc<Conversions>>,
+    metrics: &State<Arc<Metrics>>,
+    content_type: Option<&ContentType>,
     data: Data<'_>,
-    height: Option<usize>,
-    // mediatype: String,
-    invert: bool,
-    uniform: bool,
-) -> String {
-    // Buffer to store video data.
-    let mut buffer = Vec::new();
-    data.open(ByteUnit::Megabyte(100))
-        .read_to_end(&mut buffer)
-        .await
-        .unwrap();
-
-    
-
-    let hash = xxh3_128(&buffer);
-    
-    // Save the received video data to a temporary file
-    let temp_dir = env::temp_dir();
-    let audio_path = temp_dir.join(format!("received_audio:{}.mp3", hash)).to_string_lossy().to_string();
-
-    std::fs::write(&audio_path, buffer).unwrap();
-    
-    let ascii_wave = AsciiAudio::new_parallel(&audio_path, height.unwrap_or(255), uniform, invert, Verbosity::Normal);
-
-    #[allow(unused_must_use)]
-    fs::remove_file(audio_path);
-
-    let ascii = match ascii_wave {
-        Ok(ascii_wave) => ascii_wave.to_string(),
-        Err(e) => format!("error: {}", e),
+    options: ConversionOptions,
+) -> Result<Cached, ApiError> {
+    // Buffer to store audio data.
+    let upload = receive_upload(data, content_type, upload_limit(limits, "audio", DEFAULT_AUDIO_LIMIT), "audio").await?;
+    let (buffer, format) = (upload.bytes, upload.format);
+    let options = options.or(upload.options);
+
+    check_dimensions(options.height, options.width)?;
+    let parallelism = conversions.parallelism(options.parallel, options.threads)?;
+    let (height, width) = (options.height.unwrap_or(255), options.width);
+    let invert = options.invert.unwrap_or(false);
+    let uniform = options.uniform.unwrap_or(false);
+
+    let key = CacheKey::new("audio", &buffer, &[
+        ("format", format.as_ref().map(|x| x as &dyn Display)),
+        ("height", Some(&height)),
+        ("width", width.as_ref().map(|x| x as &dyn Display)),
+        ("invert", Some(&invert)),
+        ("uniform", Some(&uniform)),
+        ("start", options.start.as_ref().map(|x| x as &dyn Display)),
+        ("end", options.end.as_ref().map(|x| x as &dyn Display)),
+        ("track", options.track.as_ref().map(|x| x as &dyn Display)),
+    ]);
+
+    let audio_options = AudioOptions {
+        start: options.start,
+        end: options.end,
+        format,
+        track: options.track,
+        raw: None,
     };
 
-    ascii
+    Cached::resolve(cache, conversions, metrics, parallelism, key, &if_none_match, move |parallel| {
+        // the upload is decoded straight from memory, no temp file needed.
+        let ascii_wave = if parallel {
+            AsciiAudio::new_parallel(buffer, height, width, uniform, invert, audio_options, Verbosity::Silent)
+        } else {
+            AsciiAudio::new_sequential(buffer, height, width, uniform, invert, audio_options, Verbosity::Silent)
+        };
+
+        Ok(AsciiResponse::audio(&ascii_wave?))
+    }).await
 }
 
-#[post("/api/audio_to_ascii?<height>&<invert>&<uniform>", data = "<data>")]
-pub async fn api_audio_to_ascii_sequential(
+#[utoipa::path(
+    tag = "convert",
+    summary = "Convert an image, a video or audio to ASCII art, whichever it is",
+    description = "The kind of media is found out from the first bytes of the upload, then from its file name or content type, then by trying to read it as each kind in turn. Each option only counts for the kinds it makes sense for.",
+    request_body(
+        description = "the media, as the raw body or as the `file` part of a form, with an optional `options` part",
+        content((RawUpload = "application/octet-stream"), (MultipartUpload = "multipart/form-data")),
+    ),
+    params(ConversionOptions),
+    responses(
+        (status = 200, description = "the ASCII art, like the endpoint of its kind would give it", content(
+            (AsciiResponse = "application/json"),
+            (String = "text/plain"),
+            (String = "text/html"),
+            (String = "image/svg+xml"),
+        ), headers(("ETag" = String), ("X-Cache" = String, description = "`HIT` or `MISS`"))),
+        (status = 304, description = "the `If-None-Match` header already holds the result"),
+        (status = "4XX", description = "bad options, or an upload that's too big for its kind, unrecognized or broken", body = ApiError, content_type = "application/problem+json"),
+        (status = "5XX", description = "the conversion failed or took too long", body = ApiError, content_type = "application/problem+json"),
+    ),
+)]
+#[post("/api/convert?<options..>", data = "<data>")]
+pub async fn api_convert(
+    _key: ApiKey,
+    cache: &State<ResultCache>,
+    if_none_match: IfNoneMatch,
+    limits: &Limits,
+    conversions: &State<Arc<Conversions>>,
+    metrics: &State<Arc<Metrics>>,
+    content_type: Option<&ContentType>,
     data: Data<'_>,
-    height: Option<usize>,
-    invert: bool,
-    uniform: bool,
-) -> String {
-    // Buffer to store video data.
-    let mut buffer = Vec::new();
-    data.open(ByteUnit::Megabyte(100))
-        .read_to_end(&mut buffer)
-        .await
-        .unwrap();
-
-    
-
-    let hash = xxh3_128(&buffer);
-    
-    // Save the received video data to a temporary file
-    let temp_dir = env::temp_dir();
-    let audio_path = temp_dir.join(format!("received_audio:.{}.mp3", hash)).to_string_lossy().to_string();
-
-    std::fs::write(&audio_path, buffer).unwrap();
-    
-    let ascii_wave = AsciiAudio::new_sequential(&audio_path, height.unwrap_or(255), uniform, invert, Verbosity::Normal);
-
-
-    #[allow(unused_must_use)]
-    fs::remove_file(audio_path);
-
-    let ascii = match ascii_wave {
-        Ok(ascii_wave) => ascii_wave.to_string(),
-        Err(e) => format!("error: {}", e),
+    options: ConversionOptions,
+) -> Result<Cached, ApiError> {
+    // the kind isn't known yet, so the upload can be as big as the biggest limit; the limit of its kind gets checked
+    // once it's known.
+    let image_limit = upload_limit(limits, "image", DEFAULT_IMAGE_LIMIT);
+    let video_limit = upload_limit(limits, "video", DEFAULT_VIDEO_LIMIT);
+    let audio_limit = upload_limit(limits, "audio", DEFAULT_AUDIO_LIMIT);
+    let (limit, what) = [(image_limit, "image"), (video_limit, "video"), (audio_limit, "audio")]
+        .into_iter()
+        .max_by_key(|(limit, _)| *limit)
+        .unwrap_or((DEFAULT_VIDEO_LIMIT, "video"));
+
+    let upload = receive_upload(data, content_type, limit, what).await?;
+    let (buffer, format) = (upload.bytes, upload.format);
+    let options = options.or(upload.options);
+
+    check_dimensions(options.height, options.width)?;
+    let parallelism = conversions.parallelism(options.parallel, options.threads)?;
+
+    let key = CacheKey::new("convert", &buffer, &[
+        ("format", format.as_ref().map(|x| x as &dyn Display)),
+        ("height", options.height.as_ref().map(|x| x as &dyn Display)),
+        ("width", options.width.as_ref().map(|x| x as &dyn Display)),
+        ("nframes", options.nframes.as_ref().map(|x| x as &dyn Display)),
+        ("invert", Some(&options.invert.unwrap_or(false))),
+        ("colored", Some(&options.colored.unwrap_or(false))),
+        ("uniform", Some(&options.uniform.unwrap_or(false))),
+        ("start", options.start.as_ref().map(|x| x as &dyn Display)),
+        ("end", options.end.as_ref().map(|x| x as &dyn Display)),
+        ("track", options.track.as_ref().map(|x| x as &dyn Display)),
+    ]);
+
+    let mut convert_options = ConvertOptions {
+        height: options.height,
+        width: options.width,
+        n_frames: options.nframes,
+        invert: options.invert.unwrap_or(false),
+        colored: options.colored.unwrap_or(false),
+        uniform: options.uniform.unwrap_or(false),
+        parallel: false,
+        audio: AudioOptions {
+            start: options.start,
+            end: options.end,
+            format: None,
+            track: options.track,
+            raw: None,
+        },
     };
 
-    ascii
+    Cached::resolve(cache, conversions, metrics, parallelism, key, &if_none_match, move |parallel| {
+        // probing can mean decoding, so it happens along with the conversion.
+        let kind = detect_bytes(&buffer, format.as_deref())?;
+
+        let (limit, what) = match kind {
+            MediaKind::Image => (image_limit, "image"),
+            MediaKind::Video => (video_limit, "video"),
+            MediaKind::Audio => (audio_limit, "audio"),
+        };
+        if buffer.len() as u64 > limit.as_u64() {
+            return Err(too_large(limit, what));
+        }
+
+        convert_options.parallel = parallel;
+        let media = convert_detected(kind, buffer, format.as_deref(), &convert_options, Verbosity::Silent)?;
+
+        Ok(AsciiResponse::media(&media, FRAME_SEPARATOR))
+    }).await
 }
 
-// apk ...........................................
\ No newline at end of file
+// apk ...........................................
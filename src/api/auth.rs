@@ -0,0 +1,408 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+use rocket::{
+    catch,
+    data::ByteUnit,
+    figment::{
+        providers::{Format, Toml},
+        Figment,
+    },
+    http::{Header, Method, Status},
+    request::{FromRequest, Outcome, Request},
+    response::{self, Responder, Response},
+};
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh3::xxh3_128;
+
+use crate::{report, timestamp, utils::utils::Verbosity};
+
+use super::{config::ServerSettings, response::ApiError};
+
+/// how often the keys file gets checked for changes, at most.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// ## Info
+/// how much a single API key can do per minute; 0 means no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimit {
+    pub requests_per_minute: u64,
+    /// counted from the `Content-Length` of the uploads.
+    pub bytes_per_minute: ByteUnit,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            requests_per_minute: 0,
+            bytes_per_minute: ByteUnit::Byte(0),
+        }
+    }
+}
+
+/// ## Info
+/// the contents of the keys file. The limits at the top apply to every key that doesn't set its own, and default to
+/// the server's `rate_limit`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct KeysFile {
+    requests_per_minute: Option<u64>,
+    bytes_per_minute: Option<ByteUnit>,
+    keys: Vec<KeyEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyEntry {
+    key: String,
+    name: Option<String>,
+    requests_per_minute: Option<u64>,
+    bytes_per_minute: Option<ByteUnit>,
+}
+
+struct Client {
+    name: String,
+    limit: RateLimit,
+}
+
+/// ## Info
+/// a token bucket holding up to a minute worth of its limit, refilled continuously.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(per_minute: u64, now: Instant) -> Self {
+        Bucket {
+            tokens: per_minute as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, per_minute: u64, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_minute as f64 / 60.0).min(per_minute as f64);
+        self.updated = now;
+    }
+
+    /// ## Info
+    /// how long until `cost` tokens are available; zero if they already are.
+    fn wait(&self, cost: u64, per_minute: u64) -> Duration {
+        let missing = cost as f64 - self.tokens;
+
+        if per_minute == 0 || missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing * 60.0 / per_minute as f64)
+        }
+    }
+}
+
+struct KeyState {
+    clients: HashMap<String, Client>,
+    /// the request and byte buckets of every key that was used.
+    buckets: HashMap<String, (Bucket, Bucket)>,
+    modified: Option<SystemTime>,
+    checked: Instant,
+}
+
+/// ## Info
+/// the API keys allowed to call `/api/*`, from `api_keys` and the `api_keys_file`, along with their rate limits.
+/// Without any of the two, the api is open to anyone.
+///
+/// ## Note
+/// the keys file gets reloaded as soon as it changes; if it can't be read anymore, the keys loaded last stay in use.
+pub struct ApiKeys {
+    enabled: bool,
+    env_keys: Vec<String>,
+    file: Option<PathBuf>,
+    default_limit: RateLimit,
+    state: Mutex<KeyState>,
+}
+
+impl ApiKeys {
+    pub fn new(settings: &ServerSettings) -> Result<Self, rocket::figment::Error> {
+        let keys = ApiKeys {
+            enabled: settings.api_keys_file.is_some() || !settings.api_keys.is_empty(),
+            env_keys: settings.api_keys.clone(),
+            file: settings.api_keys_file.as_ref().map(PathBuf::from),
+            default_limit: settings.rate_limit,
+            state: Mutex::new(KeyState {
+                clients: HashMap::new(),
+                buckets: HashMap::new(),
+                modified: None,
+                checked: Instant::now(),
+            }),
+        };
+
+        let (clients, modified) = keys.load()?;
+        {
+            let mut state = keys.state.lock().unwrap();
+            state.clients = clients;
+            state.modified = modified;
+        }
+
+        Ok(keys)
+    }
+
+    /// ## Info
+    /// reads the keys from the settings and the keys file, along with when the file was last modified.
+    fn load(&self) -> Result<(HashMap<String, Client>, Option<SystemTime>), rocket::figment::Error> {
+        let mut clients = self
+            .env_keys
+            .iter()
+            .map(|key| {
+                let client = Client {
+                    name: "env".to_string(),
+                    limit: self.default_limit,
+                };
+                (key.clone(), client)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let Some(path) = &self.file else {
+            return Ok((clients, None));
+        };
+
+        let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("couldn't read the API keys from {}: {}", path.display(), e))?;
+        let file = Figment::from(Toml::string(&contents)).extract::<KeysFile>()?;
+
+        for (index, entry) in file.keys.into_iter().enumerate() {
+            let limit = RateLimit {
+                requests_per_minute: entry
+                    .requests_per_minute
+                    .or(file.requests_per_minute)
+                    .unwrap_or(self.default_limit.requests_per_minute),
+                bytes_per_minute: entry
+                    .bytes_per_minute
+                    .or(file.bytes_per_minute)
+                    .unwrap_or(self.default_limit.bytes_per_minute),
+            };
+            let name = entry.name.unwrap_or_else(|| format!("key {}", index + 1));
+
+            clients.insert(entry.key, Client { name, limit });
+        }
+
+        Ok((clients, modified))
+    }
+
+    /// ## Info
+    /// reloads the keys file if it changed since it was last read.
+    fn reload(&self, state: &mut KeyState) {
+        let Some(path) = &self.file else {
+            return;
+        };
+
+        let now = Instant::now();
+        if now.duration_since(state.checked) < RELOAD_INTERVAL {
+            return;
+        }
+        state.checked = now;
+
+        let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        if modified.is_some() && modified == state.modified {
+            return;
+        }
+
+        match self.load() {
+            Ok((clients, modified)) => {
+                report!(Verbosity::Normal, @normal "reloaded {} API keys from {}", (clients.len()), (path.display()));
+                state.buckets.retain(|key, _| clients.contains_key(key));
+                state.clients = clients;
+                state.modified = modified;
+            }
+            Err(e) => {
+                report!(Verbosity::Normal, @normal "keeping the old API keys: {}", e);
+                state.modified = modified;
+            }
+        }
+    }
+
+    /// ## Info
+    /// checks the bearer `token` of a request, and takes the request and its `bytes` out of the key's limits.
+    ///
+    /// ## Args
+    /// `bytes`: the `Content-Length` of the request, if it has a body.
+    ///
+    /// `has_body`: whether the request is expected to upload something.
+    pub fn check(&self, token: Option<&str>, bytes: Option<u64>, has_body: bool) -> Result<ApiKey, Rejection> {
+        if !self.enabled {
+            return Ok(ApiKey { name: None, id: None });
+        }
+
+        let mut state = self.state.lock().unwrap();
+        self.reload(&mut state);
+
+        let Some(token) = token else {
+            return Err(Rejection::new(Status::Unauthorized, "this server needs an `Authorization: Bearer <key>` header"));
+        };
+        let Some(client) = state.clients.get(token) else {
+            return Err(Rejection::new(Status::Unauthorized, "unknown API key"));
+        };
+        let (name, limit) = (client.name.clone(), client.limit);
+
+        let byte_limit = limit.bytes_per_minute.as_u64();
+        let bytes = match bytes {
+            Some(bytes) => bytes,
+            None if has_body && byte_limit > 0 => {
+                return Err(Rejection::new(Status::LengthRequired, "uploads need a `Content-Length` header with this API key"));
+            }
+            None => 0,
+        };
+        if byte_limit > 0 && bytes > byte_limit {
+            return Err(Rejection::new(
+                Status::PayloadTooLarge,
+                format!("the upload is bigger than the {} per minute this API key can upload", limit.bytes_per_minute),
+            ));
+        }
+
+        let now = Instant::now();
+        let (requests, uploads) = state.buckets.entry(token.to_string()).or_insert_with(|| {
+            (Bucket::full(limit.requests_per_minute, now), Bucket::full(byte_limit, now))
+        });
+        requests.refill(limit.requests_per_minute, now);
+        uploads.refill(byte_limit, now);
+
+        let wait = requests
+            .wait(1, limit.requests_per_minute)
+            .max(uploads.wait(bytes, byte_limit));
+        if !wait.is_zero() {
+            let mut rejection = Rejection::new(
+                Status::TooManyRequests,
+                format!("the API key `{}` went over its rate limit", name),
+            );
+            rejection.retry_after = Some(wait.as_secs_f64().ceil() as u64);
+            return Err(rejection);
+        }
+
+        if limit.requests_per_minute > 0 {
+            requests.tokens -= 1.0;
+        }
+        if byte_limit > 0 {
+            uploads.tokens -= bytes as f64;
+        }
+
+        Ok(ApiKey { name: Some(name), id: Some(xxh3_128(token.as_bytes())) })
+    }
+}
+
+/// ## Info
+/// the API key a request was made with: a request guard that fails with 401 without a valid key, and with 429 once
+/// the key goes over its rate limit.
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    /// the name of the key; `None` if the server doesn't need keys. Names can repeat, eg: every key from the
+    /// environment is called `env`.
+    pub name: Option<String>,
+    /// the hash of the key itself, telling apart keys that share a name; `None` if the server doesn't need keys.
+    pub id: Option<u128>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiKey {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        // checked once per request, so forwarding between routes doesn't count twice.
+        let checked = request.local_cache(|| {
+            let Some(keys) = request.rocket().state::<ApiKeys>() else {
+                return Ok(ApiKey { name: None, id: None });
+            };
+
+            let token = request
+                .headers()
+                .get_one("Authorization")
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .map(|token| token.trim());
+            let bytes = request
+                .headers()
+                .get_one("Content-Length")
+                .and_then(|value| value.parse().ok());
+
+            keys.check(token, bytes, request.method() == Method::Post)
+        });
+
+        match checked {
+            Ok(key) => Outcome::Success(key.clone()),
+            Err(rejection) => Outcome::Error((rejection.error.status, ())),
+        }
+    }
+}
+
+/// ## Info
+/// why a request didn't get through: a problem response with `WWW-Authenticate` on 401, and `Retry-After` on 429.
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    pub error: ApiError,
+    /// in seconds.
+    pub retry_after: Option<u64>,
+}
+
+impl Rejection {
+    pub fn new(status: Status, detail: impl Into<String>) -> Self {
+        Rejection {
+            error: ApiError::new(status, detail),
+            retry_after: None,
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Rejection {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.error.status;
+        let mut response = Response::build_from(self.error.respond_to(request)?);
+
+        if status == Status::Unauthorized {
+            response.header(Header::new("WWW-Authenticate", "Bearer"));
+        }
+        if let Some(retry_after) = self.retry_after {
+            response.header(Header::new("Retry-After", retry_after.to_string()));
+        }
+
+        response.ok()
+    }
+}
+
+/// ## Info
+/// answers every failed `/api/*` request with a problem response, including the ones turned down by [`ApiKey`].
+#[catch(default)]
+pub fn api_catcher(status: Status, request: &Request<'_>) -> Rejection {
+    match request.local_cache(|| Ok::<ApiKey, Rejection>(ApiKey { name: None, id: None })) {
+        Err(rejection) if rejection.error.status == status => rejection.clone(),
+        _ => Rejection::new(status, status.reason_lossy()),
+    }
+}
+
+#[test]
+fn test_rate_limit() {
+    let settings = ServerSettings {
+        api_keys: vec!["secret".to_string()],
+        rate_limit: RateLimit {
+            requests_per_minute: 2,
+            bytes_per_minute: ByteUnit::Kilobyte(1),
+        },
+        ..ServerSettings::default()
+    };
+    let keys = ApiKeys::new(&settings).unwrap();
+
+    assert_eq!(keys.check(None, None, false).unwrap_err().error.status, Status::Unauthorized);
+    assert_eq!(keys.check(Some("wrong"), None, false).unwrap_err().error.status, Status::Unauthorized);
+    assert_eq!(keys.check(Some("secret"), None, true).unwrap_err().error.status, Status::LengthRequired);
+    assert_eq!(keys.check(Some("secret"), Some(2000), true).unwrap_err().error.status, Status::PayloadTooLarge);
+
+    assert!(keys.check(Some("secret"), Some(600), true).is_ok());
+    let rejection = keys.check(Some("secret"), Some(600), true).unwrap_err();
+    assert_eq!(rejection.error.status, Status::TooManyRequests);
+    assert!(rejection.retry_after.is_some_and(|seconds| seconds >= 1));
+
+    assert!(keys.check(Some("secret"), None, false).is_ok());
+    assert!(keys.check(Some("secret"), None, false).is_err());
+}
@@ -0,0 +1,487 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Instant, SystemTime},
+};
+
+use rocket::{
+    get,
+    http::{Header, Status},
+    request::{FromRequest, Outcome, Request},
+    response::{self, Responder, Response},
+    serde::json::Json,
+    State,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+use xxhash_rust::xxh3::xxh3_128;
+
+use super::{
+    auth::ApiKey,
+    config::{Conversions, Parallelism},
+    metrics::Metrics,
+    response::{preferred_format, ApiError, AsciiResponse, Preferred},
+};
+use crate::core::render::Grid;
+
+/// ## Info
+/// identifies a conversion result: the xxh3 hash of the uploaded media together with the conversion parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheKey(u128);
+
+impl CacheKey {
+    /// ## Args
+    /// `kind`: what the media gets converted as, eg: `image`.
+    ///
+    /// `params`: every parameter that changes the output, always in the same order; missing values count as `auto`.
+    pub fn new(kind: &str, media: &[u8], params: &[(&str, Option<&dyn Display>)]) -> Self {
+        let params = params
+            .iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("{}={}", name, value),
+                None => format!("{}=auto", name),
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+
+        CacheKey(xxh3_128(format!("{}:{:032x}?{}", kind, xxh3_128(media), params).as_bytes()))
+    }
+
+    /// ## Info
+    /// the key as a quoted strong `ETag` for the representation the result is sent as; the JSON, the plain text,
+    /// the HTML and the SVG of the same result all get a different one.
+    pub(crate) fn etag(&self, representation: Preferred) -> String {
+        format!("\"{:032x}-{}\"", self.0, representation.name())
+    }
+}
+
+/// ## Info
+/// a size-capped, least recently used cache of conversion results, optionally backed by a directory so results
+/// survive restarts. Both the memory and the directory are kept under `max_bytes`.
+///
+/// ## Note
+/// only the files named after a cache key (`<32 hex digits>.json`) in the directory count as results; anything else
+/// in it is left alone.
+pub struct ResultCache {
+    entries: Mutex<Entries>,
+    dir: Option<PathBuf>,
+    files: Mutex<Files>,
+    max_bytes: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// ## Info
+/// the results in memory, with the `clock` of when they were last used.
+#[derive(Default)]
+struct Entries {
+    map: HashMap<CacheKey, (AsciiResponse, u64)>,
+    /// the keys of `map` by when they were last used, so the least recently used one is the first.
+    order: BTreeMap<u64, CacheKey>,
+    bytes: usize,
+    clock: u64,
+}
+
+/// ## Info
+/// the results in the cache directory, with how big they are and the `clock` of when they were written.
+#[derive(Default)]
+struct Files {
+    map: HashMap<CacheKey, (u64, usize)>,
+    /// the keys of `map` by when they were written, so the oldest one is the first.
+    order: BTreeMap<u64, CacheKey>,
+    bytes: usize,
+    clock: u64,
+}
+
+/// ## Info
+/// what `GET /api/cache` answers with.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: usize,
+    pub max_bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+}
+
+fn entry_size(response: &AsciiResponse) -> usize {
+    response.text.len()
+        + response.timestamps.as_ref().map_or(0, |timestamps| timestamps.len() * 8)
+        + response.grids.iter().map(Grid::bytes).sum::<usize>()
+}
+
+impl ResultCache {
+    /// ## Args
+    /// `max_bytes`: how much ASCII art to keep; 0 turns the cache off.
+    ///
+    /// `dir`: where to also keep results on disk; it gets created if it doesn't exist.
+    pub fn new(max_bytes: usize, dir: Option<PathBuf>) -> Self {
+        let mut files = Files::default();
+        if let Some(dir) = &dir {
+            let _ = fs::create_dir_all(dir);
+            files = Files::scan(dir);
+        }
+
+        ResultCache {
+            entries: Mutex::new(Entries::default()),
+            dir,
+            files: Mutex::new(files),
+            max_bytes,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: CacheKey) -> Option<AsciiResponse> {
+        if self.max_bytes == 0 {
+            return None;
+        }
+
+        let found = {
+            let mut entries = self.entries.lock().unwrap();
+            entries.clock += 1;
+            let clock = entries.clock;
+
+            let found = entries.map.get_mut(&key).map(|(response, used)| {
+                let last = std::mem::replace(used, clock);
+                (response.clone(), last)
+            });
+            found.map(|(response, last)| {
+                entries.order.remove(&last);
+                entries.order.insert(clock, key);
+                response
+            })
+        };
+
+        // results only found on disk get loaded back into memory.
+        let found = found.or_else(|| {
+            let (mut response, grids) = serde_json::from_slice::<(AsciiResponse, Vec<Grid>)>(&fs::read(self.path(key)?).ok()?).ok()?;
+            response.grids = grids;
+            self.insert_memory(key, response.clone());
+            Some(response)
+        });
+
+        match found {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+
+        found
+    }
+
+    pub fn insert(&self, key: CacheKey, response: AsciiResponse) {
+        if self.max_bytes == 0 || entry_size(&response) > self.max_bytes {
+            return;
+        }
+
+        if let (Some(path), Ok(json)) = (self.path(key), serde_json::to_vec(&(&response, &response.grids))) {
+            let len = json.len();
+            if fs::write(path, json).is_ok() {
+                self.insert_file(key, len);
+            }
+        }
+
+        self.insert_memory(key, response);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().unwrap();
+
+        CacheStats {
+            entries: entries.map.len(),
+            bytes: entries.bytes,
+            max_bytes: self.max_bytes,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            dir: self.dir.as_ref().map(|dir| dir.to_string_lossy().to_string()),
+        }
+    }
+
+    fn insert_memory(&self, key: CacheKey, response: AsciiResponse) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let (clock, size) = (entries.clock, entry_size(&response));
+
+        if let Some((old, used)) = entries.map.insert(key, (response, clock)) {
+            entries.bytes -= entry_size(&old);
+            entries.order.remove(&used);
+        }
+        entries.order.insert(clock, key);
+        entries.bytes += size;
+
+        while entries.bytes > self.max_bytes {
+            let Some((_, oldest)) = entries.order.pop_first() else {
+                break;
+            };
+            if let Some((evicted, _)) = entries.map.remove(&oldest) {
+                entries.bytes -= entry_size(&evicted);
+            }
+        }
+    }
+
+    fn path(&self, key: CacheKey) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{:032x}.json", key.0)))
+    }
+
+    /// ## Info
+    /// records a result just written to the cache directory, then removes the least recently written ones until the
+    /// directory fits in `max_bytes`.
+    fn insert_file(&self, key: CacheKey, len: usize) {
+        let mut files = self.files.lock().unwrap();
+        files.clock += 1;
+        let clock = files.clock;
+
+        if let Some((written, old)) = files.map.insert(key, (clock, len)) {
+            files.bytes -= old;
+            files.order.remove(&written);
+        }
+        files.order.insert(clock, key);
+        files.bytes += len;
+
+        while files.bytes > self.max_bytes {
+            let Some((_, oldest)) = files.order.pop_first() else {
+                break;
+            };
+            if let (Some((_, len)), Some(path)) = (files.map.remove(&oldest), self.path(oldest)) {
+                let _ = fs::remove_file(path);
+                files.bytes -= len;
+            }
+        }
+    }
+}
+
+impl Files {
+    /// ## Info
+    /// finds the results already in `dir`, eg: from before a restart.
+    fn scan(dir: &Path) -> Self {
+        let mut files = Files::default();
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return files;
+        };
+
+        let mut found = read_dir
+            .flatten()
+            .filter_map(|entry| {
+                let key = key_of_file(&entry.path())?;
+                let metadata = entry.metadata().ok()?;
+                Some((metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH), key.0, metadata.len() as usize))
+            })
+            .collect::<Vec<_>>();
+        found.sort();
+
+        for (written, (_, key, len)) in found.into_iter().enumerate() {
+            files.map.insert(CacheKey(key), (written as u64, len));
+            files.order.insert(written as u64, CacheKey(key));
+            files.bytes += len;
+        }
+        files.clock = files.map.len() as u64;
+
+        files
+    }
+}
+
+/// ## Info
+/// the cache key a file in the cache directory holds the result of, if it's named like one.
+fn key_of_file(path: &Path) -> Option<CacheKey> {
+    if path.extension()? != "json" {
+        return None;
+    }
+
+    let stem = path.file_stem()?.to_str()?;
+    if stem.len() != 32 || !stem.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u128::from_str_radix(stem, 16).ok().map(CacheKey)
+}
+
+/// ## Info
+/// the `If-None-Match` header of the request, if any.
+///
+/// ## Note
+/// `*` never matches: the conversions are all `POST`s, which shouldn't get a `304` for a result the server may never
+/// have made.
+pub struct IfNoneMatch {
+    header: Option<String>,
+    /// the representation the client asked for with its `Accept` header.
+    representation: Preferred,
+}
+
+impl IfNoneMatch {
+    pub fn matches(&self, key: CacheKey) -> bool {
+        let etag = key.etag(self.representation);
+
+        self.header.as_ref().is_some_and(|header| {
+            header.split(',').map(|tag| tag.trim()).any(|tag| tag.trim_start_matches("W/") == etag)
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfNoneMatch {
+            header: request.headers().get_one("If-None-Match").map(|value| value.to_string()),
+            representation: preferred_format(request),
+        })
+    }
+}
+
+/// ## Info
+/// a conversion result tagged with its `ETag`, or `304 Not Modified` if the client already has it.
+pub enum Cached {
+    Fresh { response: AsciiResponse, key: CacheKey, hit: bool },
+    NotModified(CacheKey),
+}
+
+impl Cached {
+    /// ## Info
+    /// answers with `304 Not Modified` if the client already has the result, otherwise looks it up in `cache` or
+    /// runs `convert` through `conversions` and caches what it returns. The result doesn't depend on `parallelism`.
+    pub async fn resolve(
+        cache: &ResultCache,
+        conversions: &Arc<Conversions>,
+        metrics: &Metrics,
+        parallelism: Parallelism,
+        key: CacheKey,
+        if_none_match: &IfNoneMatch,
+        convert: impl FnOnce(bool) -> Result<AsciiResponse, ApiError> + Send + 'static,
+    ) -> Result<Self, ApiError> {
+        if if_none_match.matches(key) {
+            return Ok(Cached::NotModified(key));
+        }
+
+        if let Some(response) = cache.get(key) {
+            return Ok(Cached::Fresh { response, key, hit: true });
+        }
+
+        let (response, duration) = conversions
+            .run(parallelism, move |parallel| {
+                let start = Instant::now();
+                convert(parallel).map(|response| (response, start.elapsed()))
+            })
+            .await?;
+        metrics.record_conversion(&response.kind, duration, response.frames);
+        cache.insert(key, response.clone());
+
+        Ok(Cached::Fresh { response, key, hit: false })
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Cached {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let representation = preferred_format(request);
+
+        match self {
+            Cached::Fresh { response, key, hit } => Response::build_from(response.respond_to(request)?)
+                .header(Header::new("ETag", key.etag(representation)))
+                .header(Header::new("Vary", "Accept"))
+                .header(Header::new("X-Cache", if hit { "HIT" } else { "MISS" }))
+                .ok(),
+            Cached::NotModified(key) => Response::build()
+                .status(Status::NotModified)
+                .header(Header::new("ETag", key.etag(representation)))
+                .ok(),
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "server",
+    summary = "Show how the result cache is doing",
+    responses((status = 200, description = "the cache stats", body = CacheStats)),
+)]
+#[get("/api/cache")]
+pub fn api_cache_stats(_key: ApiKey, cache: &State<ResultCache>) -> Json<CacheStats> {
+    Json(cache.stats())
+}
+
+#[test]
+fn test_result_cache() {
+    let response = |text: &str| AsciiResponse {
+        kind: "image".to_string(),
+        width: text.len(),
+        height: 1,
+        frames: 1,
+        text: text.to_string(),
+        separator: None,
+        timestamps: None,
+        sample_rate: None,
+        duration: None,
+        grids: vec![],
+    };
+    let key = |value: usize| CacheKey::new("image", b"media", &[("width", Some(&value)), ("height", None)]);
+
+    assert_eq!(key(1), key(1));
+    assert_ne!(key(1), key(2));
+
+    let cache = ResultCache::new(10, None);
+    cache.insert(key(1), response("aaaa"));
+    cache.insert(key(2), response("bbbb"));
+    assert!(cache.get(key(1)).is_some());
+
+    // over the cap: the least recently used entry goes.
+    cache.insert(key(3), response("cccc"));
+    assert!(cache.get(key(2)).is_none());
+    assert_eq!(cache.get(key(1)).map(|response| response.text), Some("aaaa".to_string()));
+    assert_eq!(cache.stats().bytes, 8);
+    assert_eq!(cache.entries.lock().unwrap().order.values().copied().collect::<Vec<_>>(), vec![key(3), key(1)]);
+
+    assert_eq!(key_of_file(Path::new(&format!("cache/{:032x}.json", key(1).0))), Some(key(1)));
+    assert_eq!(key_of_file(Path::new("cache/settings.json")), None);
+    assert_eq!(key_of_file(Path::new(&format!("cache/{:032x}.txt", key(1).0))), None);
+
+    let if_none_match = |header: Option<String>, representation| IfNoneMatch { header, representation };
+    let etag = key(1).etag(Preferred::Json);
+
+    assert_ne!(etag, key(1).etag(Preferred::Html));
+    assert!(if_none_match(Some(format!("W/{}, \"x\"", etag)), Preferred::Json).matches(key(1)));
+    assert!(!if_none_match(Some(etag), Preferred::Plain).matches(key(1)));
+    assert!(!if_none_match(None, Preferred::Json).matches(key(1)));
+    assert!(!if_none_match(Some("*".to_string()), Preferred::Json).matches(key(1)));
+}
+
+#[test]
+fn test_cache_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let response = |text: &str| AsciiResponse {
+        kind: "image".to_string(),
+        width: text.len(),
+        height: 1,
+        frames: 1,
+        text: text.to_string(),
+        separator: None,
+        timestamps: None,
+        sample_rate: None,
+        duration: None,
+        grids: vec![],
+    };
+    let key = |value: usize| CacheKey::new("image", b"media", &[("width", Some(&value))]);
+    let size = serde_json::to_vec(&(response("aaaa"), Vec::<Grid>::new())).unwrap().len();
+
+    fs::write(dir.path().join("notes.json"), "not a result").unwrap();
+
+    // room for a single result on disk: writing a second one removes the first, but nothing else.
+    let cache = ResultCache::new(size + size / 2, Some(dir.path().to_path_buf()));
+    cache.insert(key(1), response("aaaa"));
+    cache.insert(key(2), response("bbbb"));
+
+    assert!(!dir.path().join(format!("{:032x}.json", key(1).0)).exists());
+    assert!(dir.path().join(format!("{:032x}.json", key(2).0)).exists());
+    assert!(dir.path().join("notes.json").exists());
+
+    // results written before a restart are found again.
+    let cache = ResultCache::new(size + size / 2, Some(dir.path().to_path_buf()));
+    assert_eq!(cache.files.lock().unwrap().bytes, size);
+    assert!(cache.get(key(2)).is_some());
+}
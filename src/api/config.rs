@@ -0,0 +1,266 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use rocket::{
+    data::{ByteUnit, Limits},
+    fairing::{Fairing, Info, Kind},
+    figment::{
+        providers::{Env, Format, Serialized, Toml},
+        Figment, Profile,
+    },
+    http::{Header, Status},
+    options,
+    tokio::{sync::Semaphore, task, time},
+    Request, Response,
+};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::{auth::RateLimit, response::ApiError};
+
+/// the upload limit for images, unless `limits.image` says otherwise.
+pub const DEFAULT_IMAGE_LIMIT: ByteUnit = ByteUnit::Megabyte(10);
+/// the upload limit for videos, unless `limits.video` says otherwise.
+pub const DEFAULT_VIDEO_LIMIT: ByteUnit = ByteUnit::Megabyte(100);
+/// the upload limit for audio, unless `limits.audio` says otherwise.
+pub const DEFAULT_AUDIO_LIMIT: ByteUnit = ByteUnit::Megabyte(100);
+
+/// ## Info
+/// the settings of the web api on top of Rocket's own (`address`, `port`, `workers`, `limits`, `tls`, ...), read
+/// from the same places: a TOML file, `ROCKET_` environment variables and the command line flags.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerSettings {
+    /// whether conversions use the parallel algorithms when a request doesn't say.
+    pub parallel: bool,
+    /// the most threads a single parallel conversion can use; 0 means one per CPU.
+    pub max_threads: usize,
+    /// how many conversions (of any kind) can run at the same time; the rest wait for a free slot.
+    pub max_conversions: usize,
+    /// how many seconds a request can spend converting before giving up with a 503; 0 means no limit.
+    ///
+    /// ## Note
+    /// jobs queued through `/api/jobs` are never timed out.
+    pub conversion_timeout: u64,
+    /// the origins allowed to call the api from a browser; `*` allows any.
+    pub cors_origins: Vec<String>,
+    /// how many megabytes of conversion results to keep cached; 0 turns the cache off.
+    pub cache_size: usize,
+    /// a directory to also keep cached results in, so they survive restarts.
+    pub cache_dir: Option<String>,
+    /// the API keys allowed to call `/api/*`, on top of the ones in `api_keys_file`.
+    pub api_keys: Vec<String>,
+    /// a TOML file with API keys and their limits; reloaded whenever it changes.
+    pub api_keys_file: Option<String>,
+    /// the limits of every API key that doesn't set its own.
+    pub rate_limit: RateLimit,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            parallel: true,
+            max_threads: 0,
+            max_conversions: 2,
+            conversion_timeout: 300,
+            cors_origins: vec![],
+            cache_size: 64,
+            cache_dir: None,
+            api_keys: vec![],
+            api_keys_file: None,
+            rate_limit: RateLimit::default(),
+        }
+    }
+}
+
+impl ServerSettings {
+    /// ## Info
+    /// gathers the settings from, in increasing priority: the defaults, the TOML file at `config_path` (or
+    /// `Rocket.toml`/`ROCKET_CONFIG` if `None`), and the `ROCKET_` environment variables. Command line flags can then
+    /// be merged on top.
+    ///
+    /// ## Note
+    /// like `Rocket.toml`, the file can have `[default]`, `[debug]` and `[release]` sections; `ROCKET_PROFILE` picks one.
+    pub fn figment(config_path: Option<&str>) -> Figment {
+        let config_path = config_path.map(|path| path.to_string()).unwrap_or(Env::var_or("ROCKET_CONFIG", "Rocket.toml"));
+
+        Figment::from(rocket::Config::default())
+            .merge(Serialized::defaults(ServerSettings::default()))
+            .merge(Toml::file(config_path).nested())
+            .merge(Env::prefixed("ROCKET_").ignore(&["PROFILE"]).global())
+            .select(Profile::from_env_or("ROCKET_PROFILE", rocket::Config::DEFAULT_PROFILE))
+    }
+}
+
+/// ## Info
+/// the upload limit named `name` (`image`, `video` or `audio`), or `default` if it isn't configured.
+pub fn upload_limit(limits: &Limits, name: &str, default: ByteUnit) -> ByteUnit {
+    limits.get(name).unwrap_or(default)
+}
+
+/// ## Info
+/// how a single conversion runs, after putting the server's default and maximum on top of what the request asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parallelism {
+    /// whether to use the parallel algorithm.
+    pub parallel: bool,
+    /// how many threads the parallel algorithm gets; always 1 for the sequential one.
+    pub threads: usize,
+}
+
+/// ## Info
+/// hands out the `max_conversions` slots every conversion has to wait for, and runs conversions on the blocking
+/// thread pool so they never hold up the async workers. Parallel conversions run on a thread pool of the size they
+/// asked for, capped at `max_threads`.
+pub struct Conversions {
+    pub(crate) permits: Arc<Semaphore>,
+    timeout: Option<Duration>,
+    parallel: bool,
+    max_threads: usize,
+    pools: Mutex<HashMap<usize, Arc<ThreadPool>>>,
+}
+
+impl Conversions {
+    pub fn new(settings: &ServerSettings) -> Self {
+        let max_threads = match settings.max_threads {
+            0 => thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1),
+            threads => threads,
+        };
+
+        Conversions {
+            permits: Arc::new(Semaphore::new(settings.max_conversions.max(1))),
+            timeout: (settings.conversion_timeout > 0).then(|| Duration::from_secs(settings.conversion_timeout)),
+            parallel: settings.parallel,
+            max_threads,
+            pools: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// ## Info
+    /// works out how a request gets converted: `parallel` and `threads` default to the server's settings, and
+    /// `threads` gets capped at `max_threads`. A single thread means the sequential algorithm.
+    pub fn parallelism(&self, parallel: Option<bool>, threads: Option<usize>) -> Result<Parallelism, ApiError> {
+        if threads == Some(0) {
+            return Err(ApiError::new(Status::BadRequest, "threads has to be at least 1"));
+        }
+
+        let threads = threads.unwrap_or(self.max_threads).min(self.max_threads);
+        let parallel = parallel.unwrap_or(self.parallel) && threads > 1;
+
+        Ok(Parallelism {
+            parallel,
+            threads: if parallel { threads } else { 1 },
+        })
+    }
+
+    /// ## Info
+    /// runs `convert` on a thread pool sized for `parallelism`, blocking until it's done. Pools are made once per size
+    /// and then reused.
+    pub fn install<T: Send>(&self, parallelism: Parallelism, convert: impl FnOnce() -> T + Send) -> Result<T, ApiError> {
+        if !parallelism.parallel {
+            return Ok(convert());
+        }
+
+        let pool = {
+            let mut pools = self.pools.lock().unwrap();
+
+            match pools.get(&parallelism.threads) {
+                Some(pool) => pool.clone(),
+                None => {
+                    let pool = ThreadPoolBuilder::new()
+                        .num_threads(parallelism.threads)
+                        .build()
+                        .map(Arc::new)
+                        .map_err(|e| ApiError::new(Status::InternalServerError, format!("couldn't start the threads: {}", e)))?;
+                    pools.insert(parallelism.threads, pool.clone());
+                    pool
+                }
+            }
+        };
+
+        Ok(pool.install(convert))
+    }
+
+    /// ## Info
+    /// waits for a free slot and runs `convert` on the blocking thread pool, failing with a 503 if the whole thing
+    /// takes longer than the timeout. `convert` gets whether to use the parallel algorithm.
+    ///
+    /// ## Note
+    /// a conversion that timed out still finishes in the background, holding on to its slot until it does.
+    pub async fn run<T: Send + 'static>(
+        self: &Arc<Self>,
+        parallelism: Parallelism,
+        convert: impl FnOnce(bool) -> Result<T, ApiError> + Send + 'static,
+    ) -> Result<T, ApiError> {
+        let conversions = self.clone();
+        let conversion = async move {
+            let permit = conversions
+                .permits
+                .clone()
+                .acquire_owned()
+                .await
+                .map_err(|_| ApiError::new(Status::ServiceUnavailable, "the server is shutting down"))?;
+
+            task::spawn_blocking(move || {
+                let converted = conversions.install(parallelism, || convert(parallelism.parallel));
+                drop(permit);
+                converted?
+            })
+            .await
+            .map_err(|e| ApiError::new(Status::InternalServerError, format!("the conversion crashed: {}", e)))?
+        };
+
+        match self.timeout {
+            Some(timeout) => time::timeout(timeout, conversion).await.map_err(|_| {
+                ApiError::new(
+                    Status::ServiceUnavailable,
+                    format!("the conversion took longer than {} seconds", timeout.as_secs()),
+                )
+            })?,
+            None => conversion.await,
+        }
+    }
+}
+
+/// ## Info
+/// adds the CORS headers to the responses to the allowed origins.
+pub struct Cors {
+    pub origins: Vec<String>,
+}
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(origin) = request.headers().get_one("Origin") else {
+            return;
+        };
+
+        if !self.origins.iter().any(|allowed| allowed == "*" || allowed == origin) {
+            return;
+        }
+
+        response.set_header(Header::new("Access-Control-Allow-Origin", origin.to_string()));
+        response.set_header(Header::new("Access-Control-Allow-Methods", "GET, POST, DELETE, OPTIONS"));
+        response.set_header(Header::new("Access-Control-Allow-Headers", "Content-Type, Accept, If-None-Match, Authorization"));
+        response.set_header(Header::new("Access-Control-Expose-Headers", "ETag, Location, X-Cache, Retry-After"));
+        response.adjoin_header(Header::new("Vary", "Origin"));
+    }
+}
+
+/// ## Info
+/// answers CORS preflight requests; the headers themselves get added by [`Cors`].
+#[options("/<_..>")]
+pub fn api_preflight() -> Status {
+    Status::NoContent
+}
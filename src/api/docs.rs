@@ -0,0 +1,118 @@
+use rocket::Route;
+use utoipa::{
+    openapi::{
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        RefOr, Schema,
+    },
+    Modify, OpenApi, ToSchema,
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+use super::{
+    api, cache,
+    cache::CacheStats,
+    jobs,
+    jobs::JobStatus,
+    metrics,
+    response::{ApiError, AsciiResponse, FrameEvent},
+    upload::ConversionOptions,
+};
+
+/// ## Info
+/// the raw bytes of an uploaded file, sent as the whole body of the request.
+#[derive(ToSchema)]
+#[schema(value_type = String, format = Binary)]
+pub struct RawUpload(pub Vec<u8>);
+
+/// ## Info
+/// an uploaded file sent as a form; its file name or content type tells its format.
+#[derive(ToSchema)]
+pub struct MultipartUpload {
+    pub file: RawUpload,
+    /// as JSON; the query string wins when both set the same option.
+    pub options: Option<ConversionOptions>,
+}
+
+/// ## Info
+/// the OpenAPI document of every route, generated from their definitions.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "anything_to_ascii",
+        description = "Turns images, videos and audio into ASCII art.",
+    ),
+    paths(
+        api::api_img_to_ascii,
+        api::api_video_to_ascii,
+        api::api_video_to_ascii_stream,
+        api::api_audio_to_ascii,
+        api::api_convert,
+        jobs::api_create_job,
+        jobs::api_job_status,
+        jobs::api_job_result,
+        jobs::api_delete_job,
+        cache::api_cache_stats,
+        metrics::healthz,
+        metrics::readyz,
+        metrics::metrics,
+    ),
+    components(schemas(RawUpload, MultipartUpload, ConversionOptions, AsciiResponse, FrameEvent, ApiError, JobStatus, CacheStats)),
+    modifiers(&ApiKeyScheme, &DocHeadings),
+    security(("api_key" = [])),
+)]
+pub struct ApiDoc;
+
+/// ## Info
+/// declares the `Authorization: Bearer <key>` header the `/api/*` routes may need.
+struct ApiKeyScheme;
+
+impl Modify for ApiKeyScheme {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+/// ## Info
+/// drops the `## Info` headings the doc comments of the schemas start with.
+struct DocHeadings;
+
+impl Modify for DocHeadings {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let Some(components) = openapi.components.as_mut() else {
+            return;
+        };
+
+        for schema in components.schemas.values_mut() {
+            if let RefOr::T(Schema::Object(object)) = schema {
+                if let Some(description) = &object.description {
+                    let lines = description.lines().filter(|line| !line.starts_with("## ")).collect::<Vec<_>>();
+                    object.description = Some(lines.join(" "));
+                }
+            }
+        }
+    }
+}
+
+/// ## Info
+/// the routes serving the OpenAPI document at `/openapi.json`, and a docs page to try it out at `/docs`, bundled in
+/// the binary so it works offline.
+pub fn docs_routes() -> Vec<Route> {
+    SwaggerUi::new("/docs/<_..>").url("/openapi.json", ApiDoc::openapi()).into()
+}
+
+#[test]
+fn test_openapi() {
+    let openapi = ApiDoc::openapi();
+    let image = &openapi.paths.paths["/api/img_to_ascii"];
+    let parameters = image.post.as_ref().unwrap().parameters.as_ref().unwrap();
+
+    for name in ["height", "width", "invert", "colored", "uniform", "parallel", "threads"] {
+        assert!(parameters.iter().any(|parameter| parameter.name == name), "{} isn't documented", name);
+    }
+    assert!(openapi.paths.paths.contains_key("/api/jobs/{id}"));
+}
@@ -0,0 +1,411 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use rocket::{
+    data::Limits,
+    delete, get,
+    http::{ContentType, Header, Status},
+    post,
+    response::Responder,
+    serde::json::Json,
+    tokio::task,
+    Data, State,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    api::{
+        auth::ApiKey,
+        docs::{MultipartUpload, RawUpload},
+        config::{upload_limit, Conversions, DEFAULT_VIDEO_LIMIT},
+        metrics::Metrics,
+        api::FRAME_SEPARATOR,
+        response::{check_dimensions, ApiError, AsciiResponse},
+        upload::{receive_upload, ConversionOptions},
+    },
+    utils::utils::Verbosity,
+    video::{source::TempVideo, video_to_ascii::{AsciiVid, FrameProgress}},
+};
+
+/// ## Info
+/// keeps track of the video conversions submitted to `/api/jobs`, and runs them on the blocking thread pool once they
+/// get one of the conversion slots, so they never hold up the async workers.
+///
+/// ## Note
+/// finished jobs keep their result in memory until they get deleted, or for `retention` at most. No more than `max_jobs`
+/// jobs are kept at once: past that, the oldest finished job gets forgotten, and if none is finished new jobs are refused.
+pub struct JobQueue {
+    jobs: Mutex<HashMap<u64, Arc<Job>>>,
+    next_id: AtomicU64,
+    retention: Duration,
+    max_jobs: usize,
+}
+
+/// ## Info
+/// how long a finished job is kept around by default.
+pub const DEFAULT_JOB_RETENTION: Duration = Duration::from_secs(15 * 60);
+
+/// ## Info
+/// how many jobs, queued, running or finished, are kept at once by default.
+pub const DEFAULT_MAX_JOBS: usize = 64;
+
+impl JobQueue {
+    pub fn new() -> Self {
+        JobQueue::with_limits(DEFAULT_JOB_RETENTION, DEFAULT_MAX_JOBS)
+    }
+
+    pub fn with_limits(retention: Duration, max_jobs: usize) -> Self {
+        JobQueue {
+            jobs: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            retention,
+            max_jobs: max_jobs.max(1),
+        }
+    }
+
+    /// ## Info
+    /// how many jobs are queued or running.
+    pub fn in_flight(&self) -> usize {
+        self.jobs
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|job| matches!(*job.state.lock().unwrap(), JobState::Queued | JobState::Running))
+            .count()
+    }
+
+    /// ## Info
+    /// the job `id`, if it was made with the same API key as `owner`; jobs of other keys, even ones with the same name,
+    /// are answered as missing.
+    fn get(&self, id: u64, owner: &ApiKey) -> Result<Arc<Job>, ApiError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        self.forget_expired(&mut jobs);
+
+        jobs.get(&id)
+            .filter(|job| job.owner == owner.id)
+            .cloned()
+            .ok_or_else(|| ApiError::new(Status::NotFound, format!("there is no job {}", id)))
+    }
+
+    /// ## Info
+    /// adds `job` to the queue, making room for it by forgetting the oldest finished job if needed.
+    fn insert(&self, job: Arc<Job>) -> Result<(), ApiError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        self.forget_expired(&mut jobs);
+
+        if jobs.len() >= self.max_jobs {
+            let oldest = jobs
+                .values()
+                .filter_map(|job| job.finished().map(|finished| (finished, job.id)))
+                .min()
+                .map(|(_, id)| id)
+                .ok_or_else(|| ApiError::new(Status::ServiceUnavailable, "too many jobs are queued, try again later"))?;
+
+            jobs.remove(&oldest);
+        }
+
+        jobs.insert(job.id, job);
+
+        Ok(())
+    }
+
+    fn forget_expired(&self, jobs: &mut HashMap<u64, Arc<Job>>) {
+        jobs.retain(|_, job| job.finished().is_none_or(|finished| finished.elapsed() < self.retention));
+    }
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        JobQueue::new()
+    }
+}
+
+struct Job {
+    id: u64,
+    /// the [`ApiKey::id`] of the key the job was made with.
+    owner: Option<u128>,
+    state: Mutex<JobState>,
+    /// when the job stopped running, however it ended.
+    finished: Mutex<Option<Instant>>,
+    progress: FrameProgress,
+}
+
+enum JobState {
+    Queued,
+    Running,
+    Done(AsciiResponse),
+    Failed(ApiError),
+    Cancelled,
+}
+
+impl JobState {
+    fn name(&self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Done(_) => "done",
+            JobState::Failed(_) => "failed",
+            JobState::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// ## Info
+/// what `GET /api/jobs/<id>` answers with.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobStatus {
+    pub id: u64,
+    /// `queued`, `running`, `done`, `failed` or `cancelled`.
+    pub status: &'static str,
+    pub frames_done: usize,
+    /// 0 until the video has been decoded.
+    pub frames_total: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Job {
+    fn new(id: u64, owner: Option<u128>) -> Self {
+        Job {
+            id,
+            owner,
+            state: Mutex::new(JobState::Queued),
+            finished: Mutex::new(None),
+            progress: FrameProgress::default(),
+        }
+    }
+
+    fn finish(&self, state: JobState) {
+        *self.state.lock().unwrap() = state;
+        *self.finished.lock().unwrap() = Some(Instant::now());
+    }
+
+    fn finished(&self) -> Option<Instant> {
+        *self.finished.lock().unwrap()
+    }
+
+    fn status(&self) -> JobStatus {
+        let state = self.state.lock().unwrap();
+
+        JobStatus {
+            id: self.id,
+            status: state.name(),
+            frames_done: self.progress.done(),
+            frames_total: self.progress.total(),
+            error: match &*state {
+                JobState::Failed(error) => Some(error.detail.clone()),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// ## Info
+/// the answer to a new job: `202 Accepted`, pointing to where its status can be followed.
+#[derive(Responder)]
+#[response(status = 202)]
+pub struct JobCreated {
+    inner: Json<JobStatus>,
+    location: Header<'static>,
+}
+
+/// ## Info
+/// queues the conversion of a video; takes the same parameters as `/api/video_to_ascii`.
+#[utoipa::path(
+    tag = "jobs",
+    summary = "Queue the conversion of a video",
+    request_body(
+        description = "the video, in any format ffmpeg can decode, as the raw body or as the `file` part of a form, with an optional `options` part",
+        content((RawUpload = "application/octet-stream"), (MultipartUpload = "multipart/form-data")),
+    ),
+    params(ConversionOptions),
+    responses(
+        (status = 202, description = "the job got queued", body = JobStatus, headers(("Location" = String, description = "where to follow the job"))),
+        (status = "4XX", description = "bad options, or an upload that's too big", body = ApiError, content_type = "application/problem+json"),
+        (status = 503, description = "too many jobs are queued or running", body = ApiError, content_type = "application/problem+json"),
+    ),
+)]
+#[post("/api/jobs?<options..>", data = "<data>")]
+pub async fn api_create_job(
+    key: ApiKey,
+    queue: &State<JobQueue>,
+    conversions: &State<Arc<Conversions>>,
+    metrics: &State<Arc<Metrics>>,
+    limits: &Limits,
+    content_type: Option<&ContentType>,
+    data: Data<'_>,
+    options: ConversionOptions,
+) -> Result<JobCreated, ApiError> {
+    let upload = receive_upload(data, content_type, upload_limit(limits, "video", DEFAULT_VIDEO_LIMIT), "video").await?;
+    let (buffer, options) = (upload.bytes, options.or(upload.options));
+
+    check_dimensions(options.height, options.width)?;
+    let parallelism = conversions.parallelism(options.parallel, options.threads)?;
+    let (height, width, nframes) = (options.height, options.width, options.nframes);
+    let invert = options.invert.unwrap_or(false);
+    let colored = options.colored.unwrap_or(false);
+    let uniform = options.uniform.unwrap_or(false);
+
+    // the temp file lives as long as the job's task, and goes away with it however the job ends.
+    let video = TempVideo::from_bytes(&buffer, upload.format.as_deref())?;
+    drop(buffer);
+
+    let id = queue.next_id.fetch_add(1, Ordering::Relaxed);
+
+    let job = Arc::new(Job::new(id, key.id));
+    queue.insert(job.clone())?;
+
+    let conversions = conversions.inner().clone();
+    let metrics = metrics.inner().clone();
+    let status = job.status();
+
+    rocket::tokio::spawn(async move {
+        // waits for one of the conversion slots; the job may get cancelled in the meantime.
+        let Ok(_permit) = conversions.permits.clone().acquire_owned().await else {
+            return;
+        };
+
+        if job.progress.is_cancelled() {
+            return;
+        }
+        *job.state.lock().unwrap() = JobState::Running;
+
+        let worker = job.clone();
+        let converted = task::spawn_blocking(move || {
+            let start = Instant::now();
+            let vid_ascii = conversions.install(parallelism, || {
+                if parallelism.parallel {
+                    AsciiVid::new_paralleled_tracked(video.path(), nframes, height, width, invert, !colored, uniform, Verbosity::Silent, &worker.progress)
+                } else {
+                    AsciiVid::new_sequential_tracked(video.path(), nframes, height, width, invert, !colored, uniform, Verbosity::Silent, &worker.progress)
+                }
+            });
+            drop(video);
+
+            let response = AsciiResponse::video(&vid_ascii??, FRAME_SEPARATOR);
+            metrics.record_conversion("video", start.elapsed(), response.frames);
+
+            Ok(response)
+        })
+        .await;
+
+        job.finish(match converted {
+            _ if job.progress.is_cancelled() => JobState::Cancelled,
+            Ok(Ok(response)) => JobState::Done(response),
+            Ok(Err(e)) => JobState::Failed(e),
+            Err(e) => JobState::Failed(ApiError::new(Status::InternalServerError, format!("the conversion crashed: {}", e))),
+        });
+    });
+
+    Ok(JobCreated {
+        inner: Json(status),
+        location: Header::new("Location", format!("/api/jobs/{}", id)),
+    })
+}
+
+#[utoipa::path(
+    tag = "jobs",
+    summary = "Follow a job",
+    params(
+        ("id", Path, description = "the id of the job"),
+    ),
+    responses(
+        (status = 200, description = "the status of the job", body = JobStatus),
+        (status = 404, description = "there is no such job", body = ApiError, content_type = "application/problem+json"),
+    ),
+)]
+#[get("/api/jobs/<id>")]
+pub fn api_job_status(key: ApiKey, queue: &State<JobQueue>, id: u64) -> Result<Json<JobStatus>, ApiError> {
+    Ok(Json(queue.get(id, &key)?.status()))
+}
+
+/// ## Info
+/// the ASCII frames of a finished job, in the same shape as `/api/video_to_ascii`; 409 if the job isn't done yet.
+#[utoipa::path(
+    tag = "jobs",
+    summary = "Get the result of a finished job",
+    params(
+        ("id", Path, description = "the id of the job"),
+    ),
+    responses(
+        (status = 200, description = "the ASCII art of every frame", content(
+            (AsciiResponse = "application/json"),
+            (String = "text/plain"),
+            (String = "text/html"),
+        )),
+        (status = "4XX", description = "there is no such job (404), or it isn't done yet (409)", body = ApiError, content_type = "application/problem+json"),
+        (status = "5XX", description = "the conversion failed", body = ApiError, content_type = "application/problem+json"),
+    ),
+)]
+#[get("/api/jobs/<id>/result")]
+pub fn api_job_result(key: ApiKey, queue: &State<JobQueue>, id: u64) -> Result<AsciiResponse, ApiError> {
+    let job = queue.get(id, &key)?;
+    let state = job.state.lock().unwrap();
+
+    match &*state {
+        JobState::Done(response) => Ok(response.clone()),
+        JobState::Failed(error) => Err(error.clone()),
+        other => Err(ApiError::new(Status::Conflict, format!("job {} is {}", id, other.name()))),
+    }
+}
+
+/// ## Info
+/// cancels the job if it's still queued or running, and forgets about it (and its result).
+#[utoipa::path(
+    tag = "jobs",
+    summary = "Cancel and forget a job",
+    params(
+        ("id", Path, description = "the id of the job"),
+    ),
+    responses(
+        (status = 204, description = "the job is gone"),
+        (status = 404, description = "there is no such job", body = ApiError, content_type = "application/problem+json"),
+    ),
+)]
+#[delete("/api/jobs/<id>")]
+pub fn api_delete_job(key: ApiKey, queue: &State<JobQueue>, id: u64) -> Result<Status, ApiError> {
+    let job = queue.get(id, &key)?;
+
+    job.progress.cancel();
+    queue.jobs.lock().unwrap().remove(&id);
+
+    Ok(Status::NoContent)
+}
+
+#[test]
+fn test_job_queue() {
+    let queue = JobQueue::with_limits(Duration::from_secs(60), 2);
+    // two keys from the environment: the same name, but not the same key.
+    let alice = ApiKey { name: Some("env".to_string()), id: Some(1) };
+    let bob = ApiKey { name: Some("env".to_string()), id: Some(2) };
+
+    let first = Arc::new(Job::new(1, alice.id));
+    queue.insert(first.clone()).unwrap();
+    queue.insert(Arc::new(Job::new(2, alice.id))).unwrap();
+
+    // only the key that made a job can see it.
+    assert!(queue.get(1, &alice).is_ok());
+    assert_eq!(queue.get(1, &bob).err().map(|e| e.status), Some(Status::NotFound));
+
+    // with no finished job to forget, there's no room for a new one.
+    assert_eq!(queue.insert(Arc::new(Job::new(3, bob.id))).unwrap_err().status, Status::ServiceUnavailable);
+
+    first.finish(JobState::Cancelled);
+    queue.insert(Arc::new(Job::new(3, bob.id))).unwrap();
+    assert!(queue.get(1, &alice).is_err());
+    assert!(queue.get(3, &bob).is_ok());
+
+    // finished jobs are forgotten once they're older than the retention.
+    let queue = JobQueue::with_limits(Duration::ZERO, 2);
+    let job = Arc::new(Job::new(1, None));
+    queue.insert(job.clone()).unwrap();
+    job.finish(JobState::Cancelled);
+    assert!(queue.get(1, &ApiKey { name: None, id: None }).is_err());
+}
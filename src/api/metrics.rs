@@ -0,0 +1,263 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use rocket::{
+    fairing::{self, Fairing, Info, Kind},
+    get,
+    http::{ContentType, Status},
+    serde::json::{serde_json::json, Json, Value},
+    Build, Request, Response, Rocket, State,
+};
+
+use crate::{report, timestamp, utils::utils::Verbosity};
+
+use super::{cache::ResultCache, jobs::JobQueue, response::ApiError};
+
+/// the upper bounds of the conversion duration histogram, in seconds.
+const DURATION_BUCKETS: [f64; 10] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0];
+
+#[derive(Default)]
+struct Conversion {
+    /// how many conversions fell in each of [`DURATION_BUCKETS`], not cumulative.
+    buckets: [u64; DURATION_BUCKETS.len()],
+    seconds: f64,
+    count: u64,
+    frames: u64,
+}
+
+/// ## Info
+/// the counters behind `/metrics`. Requests get counted by [`RequestMetrics`], conversions by whoever runs them.
+#[derive(Default)]
+pub struct Metrics {
+    /// by method, route and status.
+    requests: Mutex<BTreeMap<(String, String, u16), u64>>,
+    /// by media type.
+    conversions: Mutex<BTreeMap<String, Conversion>>,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    /// ## Args
+    /// `route`: the path of the route that answered, eg: `/api/jobs/<id>`, so every job doesn't get its own series.
+    pub fn record_request(&self, method: &str, route: &str, status: u16, bytes_in: u64, bytes_out: u64) {
+        *self
+            .requests
+            .lock()
+            .unwrap()
+            .entry((method.to_string(), route.to_string(), status))
+            .or_default() += 1;
+
+        self.bytes_in.fetch_add(bytes_in, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes_out, Ordering::Relaxed);
+    }
+
+    /// ## Args
+    /// `kind`: `image`, `video` or `audio`.
+    pub fn record_conversion(&self, kind: &str, duration: Duration, frames: usize) {
+        let mut conversions = self.conversions.lock().unwrap();
+        let conversion = conversions.entry(kind.to_string()).or_default();
+        let seconds = duration.as_secs_f64();
+
+        if let Some(bucket) = DURATION_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            conversion.buckets[bucket] += 1;
+        }
+        conversion.seconds += seconds;
+        conversion.count += 1;
+        conversion.frames += frames as u64;
+    }
+
+    /// ## Info
+    /// every metric in the Prometheus text format, along with the ones read from the job queue and the cache.
+    pub fn render(&self, jobs_in_flight: usize, cache: &ResultCache) -> String {
+        let mut text = String::new();
+
+        let _ = writeln!(text, "# HELP anything_to_ascii_requests_total HTTP requests answered, by route and status.");
+        let _ = writeln!(text, "# TYPE anything_to_ascii_requests_total counter");
+        for ((method, route, status), count) in self.requests.lock().unwrap().iter() {
+            let _ = writeln!(
+                text,
+                "anything_to_ascii_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                method, route, status, count
+            );
+        }
+
+        let conversions = self.conversions.lock().unwrap();
+        let _ = writeln!(text, "# HELP anything_to_ascii_conversion_duration_seconds How long conversions took, by media type.");
+        let _ = writeln!(text, "# TYPE anything_to_ascii_conversion_duration_seconds histogram");
+        for (kind, conversion) in conversions.iter() {
+            let mut cumulative = 0;
+            for (bound, count) in DURATION_BUCKETS.iter().zip(conversion.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    text,
+                    "anything_to_ascii_conversion_duration_seconds_bucket{{kind=\"{}\",le=\"{}\"}} {}",
+                    kind, bound, cumulative
+                );
+            }
+            let _ = writeln!(text, "anything_to_ascii_conversion_duration_seconds_bucket{{kind=\"{}\",le=\"+Inf\"}} {}", kind, conversion.count);
+            let _ = writeln!(text, "anything_to_ascii_conversion_duration_seconds_sum{{kind=\"{}\"}} {}", kind, conversion.seconds);
+            let _ = writeln!(text, "anything_to_ascii_conversion_duration_seconds_count{{kind=\"{}\"}} {}", kind, conversion.count);
+        }
+
+        let _ = writeln!(text, "# HELP anything_to_ascii_frames_converted_total Frames turned into ASCII art, by media type.");
+        let _ = writeln!(text, "# TYPE anything_to_ascii_frames_converted_total counter");
+        for (kind, conversion) in conversions.iter() {
+            let _ = writeln!(text, "anything_to_ascii_frames_converted_total{{kind=\"{}\"}} {}", kind, conversion.frames);
+        }
+
+        let stats = cache.stats();
+        let lookups = stats.hits + stats.misses;
+        let hit_ratio = if lookups == 0 { 0.0 } else { stats.hits as f64 / lookups as f64 };
+
+        for (name, kind, help, value) in [
+            ("bytes_in_total", "counter", "Bytes uploaded, by their Content-Length.", self.bytes_in.load(Ordering::Relaxed) as f64),
+            ("bytes_out_total", "counter", "Bytes sent back, streams excluded.", self.bytes_out.load(Ordering::Relaxed) as f64),
+            ("jobs_in_flight", "gauge", "Jobs queued or running.", jobs_in_flight as f64),
+            ("cache_hits_total", "counter", "Conversions answered from the cache.", stats.hits as f64),
+            ("cache_misses_total", "counter", "Conversions not found in the cache.", stats.misses as f64),
+            ("cache_hit_ratio", "gauge", "Hits over all the cache lookups so far.", hit_ratio),
+            ("cache_bytes", "gauge", "Bytes of ASCII art in the cache.", stats.bytes as f64),
+        ] {
+            let _ = writeln!(text, "# HELP anything_to_ascii_{} {}", name, help);
+            let _ = writeln!(text, "# TYPE anything_to_ascii_{} {}", name, kind);
+            let _ = writeln!(text, "anything_to_ascii_{} {}", name, value);
+        }
+
+        text
+    }
+}
+
+/// ## Info
+/// counts every request answered, along with the bytes that went in and out, into [`Metrics`].
+pub struct RequestMetrics(pub Arc<Metrics>);
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let route = request
+            .route()
+            .map(|route| route.uri.path().to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+        let bytes_in = request
+            .headers()
+            .get_one("Content-Length")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+        let bytes_out = response.body().preset_size().unwrap_or(0) as u64;
+
+        self.0
+            .record_request(request.method().as_str(), &route, response.status().code, bytes_in, bytes_out);
+    }
+}
+
+/// ## Info
+/// whether the server can convert anything: set once at launch by [`Health`].
+pub struct Readiness {
+    ffmpeg: Result<(), String>,
+}
+
+/// ## Info
+/// starts ffmpeg at launch and keeps the outcome for `/readyz`, instead of failing on the first video.
+pub struct Health;
+
+#[rocket::async_trait]
+impl Fairing for Health {
+    fn info(&self) -> Info {
+        Info {
+            name: "Health",
+            kind: Kind::Ignite,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let ffmpeg = video_rs::init().map_err(|e| e.to_string());
+        if let Err(e) = &ffmpeg {
+            report!(Verbosity::Normal, @normal "ffmpeg couldn't start, videos won't convert: {}", e);
+        }
+
+        Ok(rocket.manage(Readiness { ffmpeg }))
+    }
+}
+
+/// ## Info
+/// answers as long as the server is up.
+#[utoipa::path(
+    tag = "server",
+    summary = "Check that the server is up",
+    security(()),
+    responses((status = 200, description = "the server is up")),
+)]
+#[get("/healthz")]
+pub fn healthz() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+/// ## Info
+/// answers with 503 if the server can't convert anything.
+#[utoipa::path(
+    tag = "server",
+    summary = "Check that the server can convert videos",
+    security(()),
+    responses(
+        (status = 200, description = "ffmpeg started"),
+        (status = 503, description = "ffmpeg couldn't start", body = ApiError, content_type = "application/problem+json"),
+    ),
+)]
+#[get("/readyz")]
+pub fn readyz(readiness: &State<Readiness>) -> Result<Json<Value>, ApiError> {
+    match &readiness.ffmpeg {
+        Ok(()) => Ok(Json(json!({ "status": "ready" }))),
+        Err(e) => Err(ApiError::new(Status::ServiceUnavailable, format!("ffmpeg couldn't start: {}", e))),
+    }
+}
+
+#[utoipa::path(
+    tag = "server",
+    summary = "Prometheus metrics",
+    security(()),
+    responses((status = 200, description = "the metrics in the Prometheus text format", body = String, content_type = "text/plain")),
+)]
+#[get("/metrics")]
+pub fn metrics(metrics: &State<Arc<Metrics>>, queue: &State<JobQueue>, cache: &State<ResultCache>) -> (ContentType, String) {
+    (
+        ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+        metrics.render(queue.in_flight(), cache),
+    )
+}
+
+#[test]
+fn test_metrics() {
+    let metrics = Metrics::new();
+    metrics.record_request("GET", "/api/jobs/<id>", 200, 0, 10);
+    metrics.record_request("GET", "/api/jobs/<id>", 200, 0, 10);
+    metrics.record_conversion("video", Duration::from_millis(700), 12);
+    metrics.record_conversion("video", Duration::from_secs(1000), 3);
+
+    let text = metrics.render(1, &ResultCache::new(0, None));
+
+    assert!(text.contains("anything_to_ascii_requests_total{method=\"GET\",route=\"/api/jobs/<id>\",status=\"200\"} 2"));
+    assert!(text.contains("anything_to_ascii_conversion_duration_seconds_bucket{kind=\"video\",le=\"1\"} 1"));
+    assert!(text.contains("anything_to_ascii_conversion_duration_seconds_bucket{kind=\"video\",le=\"+Inf\"} 2"));
+    assert!(text.contains("anything_to_ascii_frames_converted_total{kind=\"video\"} 15"));
+    assert!(text.contains("anything_to_ascii_bytes_out_total 20"));
+    assert!(text.contains("anything_to_ascii_jobs_in_flight 1"));
+}
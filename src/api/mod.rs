@@ -1 +1,10 @@
-pub mod api;
\ No newline at end of file
+pub mod api;
+pub mod auth;
+pub mod cache;
+pub mod config;
+pub mod docs;
+pub mod jobs;
+pub mod metrics;
+pub mod playground;
+pub mod response;
+pub mod upload;
\ No newline at end of file
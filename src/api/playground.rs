@@ -0,0 +1,42 @@
+use rocket::{get, http::ContentType, routes, Route};
+
+/// the page itself; it only talks to the `/api/*` routes of the server serving it.
+const INDEX: &str = include_str!("playground/index.html");
+const SCRIPT: &str = include_str!("playground/playground.js");
+const STYLE: &str = include_str!("playground/playground.css");
+
+/// ## Info
+/// a page to convert files from the browser: drop a file, pick the options, and see the ASCII art with its colors, or
+/// play the frames of a video.
+#[get("/")]
+pub fn playground() -> (ContentType, &'static str) {
+    (ContentType::HTML, INDEX)
+}
+
+#[get("/playground/playground.js")]
+pub fn playground_script() -> (ContentType, &'static str) {
+    (ContentType::JavaScript, SCRIPT)
+}
+
+#[get("/playground/playground.css")]
+pub fn playground_style() -> (ContentType, &'static str) {
+    (ContentType::CSS, STYLE)
+}
+
+/// ## Info
+/// the routes serving the playground at `/`, with its assets bundled in the binary so it works offline.
+pub fn playground_routes() -> Vec<Route> {
+    routes![playground, playground_script, playground_style]
+}
+
+#[test]
+fn test_playground_assets() {
+    let paths = playground_routes().into_iter().map(|route| route.uri.path().to_string()).collect::<Vec<_>>();
+
+    // the page only loads assets that get served, and never from another host.
+    for asset in ["/playground/playground.js", "/playground/playground.css"] {
+        assert!(INDEX.contains(asset), "the page doesn't load {}", asset);
+        assert!(paths.iter().any(|path| path == asset), "{} isn't served", asset);
+    }
+    assert!(!INDEX.contains("http://") && !INDEX.contains("https://"));
+}
@@ -0,0 +1,204 @@
+"use strict";
+
+const $ = (id) => document.getElementById(id);
+
+const BASIC_COLORS = ["#000", "#c33", "#3c3", "#cc3", "#36c", "#c3c", "#3cc", "#ccc"];
+const BRIGHT_COLORS = ["#666", "#f66", "#6f6", "#ff6", "#69f", "#f6f", "#6ff", "#fff"];
+
+let file = null;
+let player = null;
+
+// turns ANSI colored text into HTML, with a span for every run of characters sharing the same color.
+function ansiToHtml(text) {
+    const escape = (s) => s.replace(/&/g, "&amp;").replace(/</g, "&lt;").replace(/>/g, "&gt;");
+    let html = "";
+    let color = null;
+    let bold = false;
+    let open = false;
+
+    for (const part of text.split(/(\x1b\[[0-9;]*m)/)) {
+        const sgr = part.match(/^\x1b\[([0-9;]*)m$/);
+        if (!sgr) {
+            if (part) html += escape(part);
+            continue;
+        }
+
+        const codes = sgr[1] === "" ? [0] : sgr[1].split(";").map(Number);
+        for (let i = 0; i < codes.length; i++) {
+            const code = codes[i];
+            if (code === 0) {
+                color = null;
+                bold = false;
+            } else if (code === 1) {
+                bold = true;
+            } else if (code === 22) {
+                bold = false;
+            } else if (code === 39) {
+                color = null;
+            } else if (code >= 30 && code <= 37) {
+                color = BASIC_COLORS[code - 30];
+            } else if (code >= 90 && code <= 97) {
+                color = BRIGHT_COLORS[code - 90];
+            } else if (code === 38 && codes[i + 1] === 2) {
+                color = `rgb(${codes[i + 2]},${codes[i + 3]},${codes[i + 4]})`;
+                i += 4;
+            } else if (code === 38 && codes[i + 1] === 5) {
+                i += 2;
+            }
+        }
+
+        if (open) html += "</span>";
+        open = color !== null || bold;
+        if (open) {
+            const style = (color ? `color:${color};` : "") + (bold ? "font-weight:bold;" : "");
+            html += `<span style="${style}">`;
+        }
+    }
+
+    if (open) html += "</span>";
+    return html;
+}
+
+function setStatus(message, isError = false) {
+    $("status").textContent = message;
+    $("status").classList.toggle("error", isError);
+}
+
+function kindOf(file) {
+    const type = file.type.split("/")[0];
+    if (type === "image" || type === "video" || type === "audio") return type;
+    if (/\.(mp4|mkv|webm|mov|avi)$/i.test(file.name)) return "video";
+    if (/\.(mp3|wav|flac|ogg|aiff|m4a)$/i.test(file.name)) return "audio";
+    // anything else gets found out by the server.
+    return "media";
+}
+
+function pickFile(picked) {
+    file = picked;
+    $("drop-text").textContent = `${file.name} (${kindOf(file)}, ${Math.ceil(file.size / 1024)} KiB)`;
+    $("convert").disabled = false;
+}
+
+function query(kind) {
+    const params = new URLSearchParams();
+    for (const name of ["width", "height"]) {
+        if ($(name).value) params.set(name, $(name).value);
+    }
+    if ((kind === "video" || kind === "media") && $("nframes").value) params.set("nframes", $("nframes").value);
+    for (const name of ["invert", "uniform"]) {
+        params.set(name, $(name).checked);
+    }
+    if (kind !== "audio") params.set("colored", $("colored").checked);
+    return params.toString();
+}
+
+async function convert() {
+    const kind = kindOf(file);
+    const endpoint = { image: "img_to_ascii", video: "video_to_ascii", audio: "audio_to_ascii", media: "convert" }[kind];
+    const form = new FormData();
+    form.append("file", file, file.name);
+
+    const headers = { Accept: "application/json" };
+    if ($("key").value) headers.Authorization = `Bearer ${$("key").value}`;
+
+    stopPlayer();
+    $("convert").disabled = true;
+    setStatus(`converting the ${kind}...`);
+    const start = performance.now();
+
+    try {
+        const response = await fetch(`/api/${endpoint}?${query(kind)}`, { method: "POST", headers, body: form });
+        const body = await response.json().catch(() => null);
+        if (!response.ok) {
+            throw new Error(body && body.detail ? body.detail : `${response.status} ${response.statusText}`);
+        }
+
+        const seconds = ((performance.now() - start) / 1000).toFixed(1);
+        setStatus(`${body.width}×${body.height} characters, ${body.frames} frame(s), in ${seconds}s`);
+
+        if (body.kind === "video") {
+            startPlayer(body.text.split(body.separator), body.timestamps);
+        } else {
+            $("output").innerHTML = ansiToHtml(body.text);
+        }
+    } catch (e) {
+        setStatus(e.message, true);
+    } finally {
+        $("convert").disabled = false;
+    }
+}
+
+// shows the frames of a video at the pace of their timestamps, looping at the end.
+function startPlayer(frames, timestamps) {
+    const html = frames.map(ansiToHtml);
+    const delays = timestamps.map((time, index) =>
+        index + 1 < timestamps.length ? (timestamps[index + 1] - time) * 1000 : 100);
+
+    player = { html, delays, index: 0, timer: null, playing: true };
+    $("player").hidden = false;
+    $("seek").max = html.length - 1;
+    $("play").textContent = "pause";
+    showFrame(0);
+    scheduleFrame();
+}
+
+function showFrame(index) {
+    player.index = index;
+    $("output").innerHTML = player.html[index];
+    $("seek").value = index;
+    $("frame-info").textContent = `${index + 1}/${player.html.length}`;
+}
+
+function scheduleFrame() {
+    clearTimeout(player.timer);
+    if (!player.playing) return;
+
+    player.timer = setTimeout(() => {
+        showFrame((player.index + 1) % player.html.length);
+        scheduleFrame();
+    }, Math.max(player.delays[player.index], 10));
+}
+
+function stopPlayer() {
+    if (player) clearTimeout(player.timer);
+    player = null;
+    $("player").hidden = true;
+}
+
+$("file").addEventListener("change", () => {
+    if ($("file").files.length) pickFile($("file").files[0]);
+});
+
+for (const event of ["dragenter", "dragover"]) {
+    $("drop").addEventListener(event, (e) => {
+        e.preventDefault();
+        $("drop").classList.add("hover");
+    });
+}
+
+for (const event of ["dragleave", "drop"]) {
+    $("drop").addEventListener(event, (e) => {
+        e.preventDefault();
+        $("drop").classList.remove("hover");
+    });
+}
+
+$("drop").addEventListener("drop", (e) => {
+    if (e.dataTransfer.files.length) pickFile(e.dataTransfer.files[0]);
+});
+
+$("convert").addEventListener("click", convert);
+
+$("play").addEventListener("click", () => {
+    player.playing = !player.playing;
+    $("play").textContent = player.playing ? "pause" : "play";
+    scheduleFrame();
+});
+
+$("seek").addEventListener("input", () => {
+    showFrame(Number($("seek").value));
+    scheduleFrame();
+});
+
+$("key").value = localStorage.getItem("anything_to_ascii.key") || "";
+$("key").addEventListener("change", () => localStorage.setItem("anything_to_ascii.key", $("key").value));
@@ -0,0 +1,359 @@
+use rocket::{
+    data::ByteUnit,
+    http::{ContentType, MediaType, Status},
+    request::Request,
+    response::{self, Responder},
+    Data,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    core::{
+        convert::AsciiMedia,
+        render::{Grid, RenderOptions},
+    },
+    prelude::{AsciiAudio, AsciiImg, AsciiVid},
+    Error,
+};
+
+/// ## Info
+/// the result of a successful conversion. Sent as JSON, as the bare ASCII art if the client prefers `text/plain`, or
+/// drawn as a page or an image if it prefers `text/html` or `image/svg+xml`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AsciiResponse {
+    /// `image`, `video` or `audio`.
+    pub kind: String,
+    /// the width of the ASCII art, in characters.
+    pub width: usize,
+    /// the height of the ASCII art, in characters.
+    pub height: usize,
+    /// the number of frames; 1 for images and audio.
+    pub frames: usize,
+    /// the ASCII art; the frames of a video are joined by `separator`.
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separator: Option<String>,
+    /// the timestamp of every frame of a video, in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamps: Option<Vec<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    /// the length of the rendered audio, in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    /// the character grid of every frame, as it came out of the conversion; the HTML and SVG are drawn from it, so
+    /// nothing gets lost going through the ANSI `text`. Never sent to clients.
+    #[serde(skip)]
+    pub(crate) grids: Vec<Grid>,
+}
+
+impl AsciiResponse {
+    pub fn image(image: &AsciiImg) -> Self {
+        AsciiResponse {
+            kind: "image".to_string(),
+            width: image.0.columns,
+            height: image.0.rows,
+            frames: 1,
+            text: image.to_string(),
+            separator: None,
+            timestamps: None,
+            sample_rate: None,
+            duration: None,
+            grids: vec![Grid::from(&image.0)],
+        }
+    }
+
+    pub fn video(video: &AsciiVid, separator: &str) -> Self {
+        let first = video.0.first();
+
+        AsciiResponse {
+            kind: "video".to_string(),
+            width: first.map(|img| img.0.columns).unwrap_or(0),
+            height: first.map(|img| img.0.rows).unwrap_or(0),
+            frames: video.0.len(),
+            text: video.0.iter().map(|img| img.to_string()).collect::<Vec<_>>().join(separator),
+            separator: Some(separator.to_string()),
+            timestamps: Some(video.1.clone()),
+            sample_rate: None,
+            duration: None,
+            grids: video.0.iter().map(|img| Grid::from(&img.0)).collect(),
+        }
+    }
+
+    pub fn audio(audio: &AsciiAudio) -> Self {
+        let text = audio.to_string();
+
+        AsciiResponse {
+            kind: "audio".to_string(),
+            width: audio.0.columns,
+            height: audio.0.rows,
+            frames: 1,
+            // a waveform has no colors, so its text is all there is to it.
+            grids: vec![Grid::from_ansi(&text)],
+            text,
+            separator: None,
+            timestamps: None,
+            sample_rate: Some(audio.1.sample_rate),
+            duration: Some(audio.1.duration),
+        }
+    }
+
+    /// ## Info
+    /// the response for media of any kind; `separator` only counts for videos.
+    pub fn media(media: &AsciiMedia, separator: &str) -> Self {
+        match media {
+            AsciiMedia::Image(image) => Self::image(image),
+            AsciiMedia::Video(video) => Self::video(video, separator),
+            AsciiMedia::Audio(audio) => Self::audio(audio),
+        }
+    }
+}
+
+/// ## Info
+/// a single converted frame, as sent by the streaming video endpoint.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FrameEvent {
+    pub index: usize,
+    /// when the frame shows up in the video, in seconds.
+    pub timestamp: f64,
+    pub width: usize,
+    pub height: usize,
+    pub text: String,
+}
+
+impl FrameEvent {
+    pub fn new(index: usize, timestamp: f64, frame: &AsciiImg) -> Self {
+        FrameEvent {
+            index,
+            timestamp,
+            width: frame.0.columns,
+            height: frame.0.rows,
+            text: frame.to_string(),
+        }
+    }
+}
+
+impl AsciiResponse {
+    /// ## Info
+    /// the ASCII art as a self-contained HTML page; a video gets a player with all of its frames.
+    pub fn html(&self, options: &RenderOptions) -> String {
+        match (&self.separator, &self.timestamps) {
+            (Some(_), Some(timestamps)) => Grid::video_html(&self.grids, timestamps, options),
+            _ => self.grid().html(options),
+        }
+    }
+
+    /// ## Info
+    /// the ASCII art as an SVG image; `None` for videos, which have more than one.
+    pub fn svg(&self, options: &RenderOptions) -> Option<String> {
+        match self.separator {
+            Some(_) => None,
+            None => Some(self.grid().svg(options)),
+        }
+    }
+
+    /// ## Info
+    /// the grid of an image or audio, which only have one.
+    fn grid(&self) -> Grid {
+        self.grids.first().cloned().unwrap_or_default()
+    }
+}
+
+impl<'r> Responder<'r, 'static> for AsciiResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let options = RenderOptions::default();
+
+        match preferred_format(request) {
+            Preferred::Plain => (ContentType::Plain, self.text).respond_to(request),
+            Preferred::Html => (ContentType::HTML, self.html(&options)).respond_to(request),
+            Preferred::Svg => match self.svg(&options) {
+                Some(svg) => (ContentType::SVG, svg).respond_to(request),
+                None => ApiError::new(Status::NotAcceptable, "videos can't be sent as a single SVG, ask for HTML instead").respond_to(request),
+            },
+            Preferred::Json => {
+                let json = serde_json::to_string(&self).map_err(|_| Status::InternalServerError)?;
+                (ContentType::JSON, json).respond_to(request)
+            }
+        }
+    }
+}
+
+/// ## Info
+/// an error sent back to the client with a matching HTTP status: as RFC 7807 problem JSON (`application/problem+json`),
+/// or as `error: <detail>` if the client prefers `text/plain`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ApiError {
+    #[serde(rename = "type")]
+    pub problem_type: &'static str,
+    pub title: &'static str,
+    #[serde(serialize_with = "serialize_status")]
+    #[schema(value_type = u16)]
+    pub status: Status,
+    pub detail: String,
+}
+
+fn serialize_status<S: serde::Serializer>(status: &Status, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u16(status.code)
+}
+
+impl ApiError {
+    pub fn new(status: Status, detail: impl Into<String>) -> Self {
+        ApiError {
+            problem_type: "about:blank",
+            title: status.reason_lossy(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(value: Error) -> Self {
+        use image::error::ImageError;
+        use symphonia::core::errors::Error as AudioError;
+
+        let status = match &value {
+            Error::ImageError(ImageError::Unsupported(_)) => Status::UnsupportedMediaType,
+            Error::ImageError(ImageError::Decoding(_)) => Status::UnprocessableEntity,
+            Error::ImageError(ImageError::Limits(_)) => Status::PayloadTooLarge,
+            Error::ImageError(_) => Status::InternalServerError,
+            Error::UnsupportedImage(_) => Status::UnsupportedMediaType,
+            Error::AudioError(AudioError::Unsupported(_)) => Status::UnsupportedMediaType,
+            Error::AudioError(AudioError::DecodeError(_) | AudioError::IoError(_)) => Status::UnprocessableEntity,
+            Error::AudioError(_) => Status::InternalServerError,
+            // ffmpeg failing on an upload almost always means the upload itself is broken.
+            Error::VideoError(_) => Status::UnprocessableEntity,
+            // the library only gives back its own messages for what's wrong with the request or the upload.
+            Error::LibError(_) | Error::GlobError(_) => Status::UnprocessableEntity,
+            Error::InternalError(_) | Error::RocketError(_) | Error::IO(_) => Status::InternalServerError,
+            Error::JsonError(_) | Error::MsgPackEncodeError(_) | Error::MsgPackDecodeError(_) => Status::InternalServerError,
+        };
+
+        ApiError::new(status, value.to_string())
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.status;
+
+        if wants_plain_text(request) {
+            return (status, (ContentType::Plain, format!("error: {}", self.detail))).respond_to(request);
+        }
+
+        let json = serde_json::to_string(&self).map_err(|_| Status::InternalServerError)?;
+        (status, (ContentType::new("application", "problem+json"), json)).respond_to(request)
+    }
+}
+
+/// ## Info
+/// what a client can ask for with its `Accept` header, instead of JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Preferred {
+    Json,
+    Plain,
+    Html,
+    Svg,
+}
+
+impl Preferred {
+    /// ## Info
+    /// a short name for the representation, used to tell their `ETag`s apart.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Preferred::Json => "json",
+            Preferred::Plain => "plain",
+            Preferred::Html => "html",
+            Preferred::Svg => "svg",
+        }
+    }
+}
+
+pub(crate) fn preferred_format(request: &Request<'_>) -> Preferred {
+    let Some(accept) = request.accept() else {
+        return Preferred::Json;
+    };
+
+    match accept.preferred().media_type() {
+        media_type if *media_type == MediaType::Plain => Preferred::Plain,
+        media_type if *media_type == MediaType::HTML => Preferred::Html,
+        media_type if *media_type == MediaType::SVG => Preferred::Svg,
+        _ => Preferred::Json,
+    }
+}
+
+/// ## Info
+/// whether the client would rather get the bare ASCII art than JSON.
+fn wants_plain_text(request: &Request<'_>) -> bool {
+    matches!(preferred_format(request), Preferred::Plain)
+}
+
+/// ## Info
+/// reads the whole request body, failing with 413 if it goes over `limit` and with 400 if it's empty.
+///
+/// ## Args
+/// `what`: the kind of media expected (`image`, `video` or `audio`); also the name of the setting for its limit.
+pub async fn read_upload(data: Data<'_>, limit: ByteUnit, what: &str) -> Result<Vec<u8>, ApiError> {
+    // one more byte than the limit, so that an upload of exactly the limit still fits.
+    let capped = data
+        .open(limit + 1)
+        .into_bytes()
+        .await
+        .map_err(|e| ApiError::new(Status::BadRequest, format!("couldn't read the upload: {}", e)))?;
+
+    if !capped.is_complete() || capped.len() as u64 > limit.as_u64() {
+        return Err(too_large(limit, what));
+    }
+
+    if capped.is_empty() {
+        return Err(ApiError::new(Status::BadRequest, "No data received."));
+    }
+
+    Ok(capped.into_inner())
+}
+
+/// ## Info
+/// the 413 for an upload of `what` that went over `limit`.
+pub fn too_large(limit: ByteUnit, what: &str) -> ApiError {
+    ApiError::new(
+        Status::PayloadTooLarge,
+        format!("the {} is bigger than the {} upload limit of this server (set by `limits.{}`)", what, limit, what),
+    )
+}
+
+/// ## Info
+/// rejects a requested height or width of 0 characters.
+pub fn check_dimensions(height: Option<usize>, width: Option<usize>) -> Result<(), ApiError> {
+    if height == Some(0) || width == Some(0) {
+        Err(ApiError::new(Status::BadRequest, "height and width have to be at least 1"))
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_render_from_grid() {
+    use crate::core::{char::ColoredChar, flat_matrix::FlatMatrix};
+
+    let red = ColoredChar::from_everything(200, (255, 0, 0), true, false, false);
+    let mut hidden = red.clone();
+    hidden.display = false;
+    let image = AsciiImg(FlatMatrix::from(vec![vec![red.clone(), hidden], vec![red.clone(), red]]));
+    let options = RenderOptions::default();
+
+    // the API draws the same thing as the CLI.
+    let response = AsciiResponse::image(&image);
+    assert_eq!(response.html(&options), image.to_html(&options));
+    assert_eq!(response.svg(&options), Some(image.to_svg(&options)));
+
+    let video = AsciiVid(vec![AsciiImg(image.0.clone()), AsciiImg(image.0.clone())], vec![0.0, 0.5]);
+    assert_eq!(AsciiResponse::video(&video, "\n").html(&options), video.to_html(&options));
+}
+
+#[test]
+fn test_error_status() {
+    assert_eq!(ApiError::from(Error::from("no track exists at the requested index")).status, Status::UnprocessableEntity);
+    assert_eq!(ApiError::from(Error::InternalError("ffmpeg couldn't start")).status, Status::InternalServerError);
+    assert_eq!(ApiError::from(Error::IO(std::io::ErrorKind::Other.into())).status, Status::InternalServerError);
+}
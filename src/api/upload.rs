@@ -0,0 +1,181 @@
+use rocket::{
+    data::ByteUnit,
+    http::{ContentType, Status},
+    Data, FromForm,
+};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use super::response::{read_upload, too_large, ApiError};
+
+/// how much a multipart upload can be on top of the limit of its file: the boundaries, the headers of the parts and
+/// the `options` part.
+const FORM_HEADROOM: u64 = 64 * 1024;
+
+/// ## Info
+/// how to convert an upload: given in the query string, or as JSON in the `options` part of a multipart upload. The
+/// query string wins when both set the same option.
+#[derive(Debug, Clone, Default, PartialEq, FromForm, Deserialize, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConversionOptions {
+    /// the height of the ASCII art in characters; defaults to the height of the media, or 255 for half of a waveform.
+    pub height: Option<usize>,
+    /// the width of the ASCII art in characters; defaults to the width of the media, or a column per audio sample up to 4096 columns.
+    pub width: Option<usize>,
+    /// videos only: how many evenly spaced frames to convert; defaults to all of them.
+    pub nframes: Option<usize>,
+    /// use the least dense characters for the most luminous spots.
+    pub invert: Option<bool>,
+    /// images and videos only: color the ASCII art with ANSI escape codes.
+    pub colored: Option<bool>,
+    /// use only the densest character (the least dense one with `invert`).
+    pub uniform: Option<bool>,
+    /// whether to use the parallel algorithm; defaults to the server's `parallel` setting.
+    pub parallel: Option<bool>,
+    /// how many threads the parallel algorithm can use, capped at `max_threads`; 1 means sequential.
+    pub threads: Option<usize>,
+    /// audio only: where to start, in seconds.
+    pub start: Option<f64>,
+    /// audio only: where to end, in seconds.
+    pub end: Option<f64>,
+    /// audio only: the index of the track to convert; defaults to the first audio track.
+    pub track: Option<usize>,
+}
+
+impl ConversionOptions {
+    /// ## Info
+    /// takes every option that isn't set from `fallback`.
+    pub fn or(self, fallback: ConversionOptions) -> Self {
+        ConversionOptions {
+            height: self.height.or(fallback.height),
+            width: self.width.or(fallback.width),
+            nframes: self.nframes.or(fallback.nframes),
+            invert: self.invert.or(fallback.invert),
+            colored: self.colored.or(fallback.colored),
+            uniform: self.uniform.or(fallback.uniform),
+            parallel: self.parallel.or(fallback.parallel),
+            threads: self.threads.or(fallback.threads),
+            start: self.start.or(fallback.start),
+            end: self.end.or(fallback.end),
+            track: self.track.or(fallback.track),
+        }
+    }
+}
+
+/// ## Info
+/// an uploaded file, along with what the client said about it.
+pub struct Upload {
+    pub bytes: Vec<u8>,
+    /// the format of the file as an extension (eg: `webm`), from its file name or its content type.
+    pub format: Option<String>,
+    /// the options from the `options` part of a multipart upload.
+    pub options: ConversionOptions,
+}
+
+/// ## Info
+/// reads an upload sent either as the raw body of the request, or as `multipart/form-data` with a `file` part and
+/// an optional `options` part holding [`ConversionOptions`] as JSON.
+///
+/// ## Args
+/// `what`: the kind of media expected (`image`, `video` or `audio`); also the name of the setting for its limit.
+pub async fn receive_upload(
+    data: Data<'_>,
+    content_type: Option<&ContentType>,
+    limit: ByteUnit,
+    what: &str,
+) -> Result<Upload, ApiError> {
+    let boundary = content_type
+        .filter(|content_type| content_type.is_form_data())
+        .and_then(|content_type| content_type.param("boundary"));
+
+    let Some(boundary) = boundary else {
+        return Ok(Upload {
+            bytes: read_upload(data, limit, what).await?,
+            format: content_type.and_then(|content_type| format_of(None, Some(content_type))),
+            options: ConversionOptions::default(),
+        });
+    };
+
+    // the limit is for the file itself; the rest of the form gets some headroom, plus one byte so multer can tell
+    // when it's gone over.
+    let size_limit = multer::SizeLimit::new()
+        .whole_stream(limit.as_u64() + FORM_HEADROOM)
+        .per_field(FORM_HEADROOM)
+        .for_field("file", limit.as_u64());
+    let constraints = multer::Constraints::new().size_limit(size_limit);
+    let stream = data.open(limit + FORM_HEADROOM + 1);
+    let mut form = multer::Multipart::with_reader_with_constraints(stream, boundary, constraints);
+
+    let mut upload = None;
+    let mut options = ConversionOptions::default();
+
+    while let Some(field) = form.next_field().await.map_err(|e| multipart_error(e, limit, what))? {
+        match field.name() {
+            Some("file") => {
+                let content_type = field
+                    .content_type()
+                    .and_then(|mime| ContentType::parse_flexible(mime.as_ref()));
+                let format = format_of(field.file_name(), content_type.as_ref());
+                let bytes = field.bytes().await.map_err(|e| multipart_error(e, limit, what))?;
+
+                upload = Some((bytes.to_vec(), format));
+            }
+            Some("options") => {
+                let json = field.bytes().await.map_err(|e| multipart_error(e, limit, what))?;
+                options = serde_json::from_slice(&json).map_err(|e| {
+                    ApiError::new(Status::BadRequest, format!("the `options` part isn't valid: {}", e))
+                })?;
+            }
+            _ => (),
+        }
+    }
+
+    match upload {
+        Some((bytes, _)) if bytes.is_empty() => Err(ApiError::new(Status::BadRequest, "No data received.")),
+        Some((bytes, format)) => Ok(Upload { bytes, format, options }),
+        None => Err(ApiError::new(Status::BadRequest, format!("the form has no `file` part with the {}", what))),
+    }
+}
+
+fn multipart_error(error: multer::Error, limit: ByteUnit, what: &str) -> ApiError {
+    match error {
+        multer::Error::StreamSizeExceeded { .. } | multer::Error::FieldSizeExceeded { .. } => too_large(limit, what),
+        e => ApiError::new(Status::BadRequest, format!("couldn't read the form: {}", e)),
+    }
+}
+
+/// ## Info
+/// the format of a file as a lowercase extension, from its name if it has one, otherwise from its content type.
+/// Generic content types like `application/octet-stream` say nothing.
+pub fn format_of(file_name: Option<&str>, content_type: Option<&ContentType>) -> Option<String> {
+    let from_name = file_name
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension.to_string());
+
+    let from_type = content_type
+        .filter(|content_type| **content_type != ContentType::Binary && !content_type.is_form_data())
+        .map(|content_type| match content_type.extension() {
+            Some(extension) => extension.to_string(),
+            None => content_type.sub().as_str().trim_start_matches("x-").to_string(),
+        });
+
+    // only plain extensions, since they end up in the names of temp files.
+    from_name
+        .or(from_type)
+        .map(|format| format.to_lowercase())
+        .filter(|format| !format.is_empty() && format.len() <= 8 && format.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+#[test]
+fn test_format_of() {
+    assert_eq!(format_of(Some("clip.WebM"), Some(&ContentType::MP4)), Some("webm".to_string()));
+    assert_eq!(format_of(None, Some(&ContentType::new("video", "x-matroska"))), Some("matroska".to_string()));
+    assert_eq!(format_of(None, Some(&ContentType::PNG)), Some("png".to_string()));
+    assert_eq!(format_of(Some("noextension"), Some(&ContentType::Binary)), None);
+    assert_eq!(format_of(Some("evil./../x"), None), None);
+
+    let query = ConversionOptions { width: Some(10), ..ConversionOptions::default() };
+    let json = serde_json::from_str::<ConversionOptions>(r#"{"width": 20, "height": 5}"#).unwrap();
+    assert_eq!(query.or(json), ConversionOptions { width: Some(10), height: Some(5), ..ConversionOptions::default() });
+}
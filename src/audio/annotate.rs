@@ -0,0 +1,237 @@
+use clap::ValueEnum;
+
+use crate::core::flat_matrix::FlatMatrix;
+
+use super::audio_to_ascii::{magnitude_to_rows, rows_to_amplitude, AsciiAudio, AudioInfo};
+
+/// ## Info
+/// the unit used by the amplitude axis of an annotated waveform.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum AmplitudeScale {
+    /// amplitude as a fraction of full scale, from -1.0 to 1.0.
+    #[default]
+    Linear,
+    /// amplitude in decibels relative to full scale.
+    Dbfs,
+}
+
+/// the character used for gridlines; it only ever replaces empty cells, so the waveform stays on top.
+pub const GRIDLINE_CHAR: char = '-';
+
+/// the candidate intervals between time ruler ticks, in seconds.
+const TICK_INTERVALS: [f64; 23] = [
+    0.001, 0.002, 0.005, 0.01, 0.02, 0.05, 0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 15.0, 30.0, 60.0, 120.0, 300.0,
+    600.0, 900.0, 1800.0, 3600.0, 7200.0,
+];
+
+impl AsciiAudio {
+    /// ## Info
+    /// renders the waveform with a title line (file name, duration and sample rate), an amplitude axis on the left,
+    /// a time ruler at the bottom and, optionally, horizontal gridlines at the given dBFS levels (eg: `-6.0`).
+    ///
+    /// ## Note
+    /// gridlines above 0 dBFS (louder than any sample can be), or that would end up outside of the waveform or on its
+    /// midpoint, are left out. The plain output is still available through [`Display`](std::fmt::Display).
+    pub fn annotated(&self, scale: AmplitudeScale, gridlines: &[f64]) -> String {
+        let info = &self.1;
+        let mut grid = self.0.clone();
+        let rows = grid.rows;
+        let midpoint = rows.saturating_sub(1) / 2;
+
+        // the offset (in rows, from the midpoint) of every gridline that fits.
+        let gridline_offsets = gridlines
+            .iter()
+            .filter(|&&level| level <= 0.0)
+            .filter_map(|level| {
+                let magnitude = (10f64.powf(level / 20.0) * i16::MAX as f64).min(i16::MAX as f64) as u16;
+                let reached = magnitude_to_rows(magnitude, midpoint);
+
+                if reached == 0 || reached > midpoint + 1 {
+                    None
+                } else {
+                    Some(reached - 1)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for offset in &gridline_offsets {
+            draw_gridline(&mut grid, midpoint - offset);
+            if midpoint + offset < rows {
+                draw_gridline(&mut grid, midpoint + offset);
+            }
+        }
+
+        let label_every = (midpoint / 4).max(1);
+        let labels = (0..rows)
+            .map(|row| {
+                let offset = row.abs_diff(midpoint);
+                let is_edge = offset == midpoint;
+
+                // the last row sits past full scale, so it never gets a label.
+                if offset > midpoint {
+                    None
+                } else if offset % label_every == 0 || is_edge || gridline_offsets.contains(&offset) {
+                    Some(amplitude_label(offset, row > midpoint, midpoint, scale))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let margin = labels.iter().flatten().map(|label| label.len()).max().unwrap_or(0);
+
+        let mut lines = Vec::with_capacity(rows + 3);
+        lines.push(title(info));
+
+        for (row, label) in grid.chunks().zip(labels) {
+            lines.push(format!(
+                "{:>margin$} |{}",
+                label.unwrap_or_default(),
+                row.iter().collect::<String>(),
+                margin = margin
+            ));
+        }
+
+        let (ruler, ruler_labels) = time_ruler(info, grid.columns);
+        lines.push(format!("{:>margin$} +{}", "", ruler, margin = margin));
+        lines.push(format!("{:>margin$}  {}", "", ruler_labels, margin = margin));
+
+        lines.join("\n")
+    }
+}
+
+fn draw_gridline(grid: &mut FlatMatrix<char>, row: usize) {
+    let columns = grid.columns;
+
+    for column in 0..columns {
+        if grid[(row, column)] == ' ' {
+            grid[(row, column)] = GRIDLINE_CHAR;
+        }
+    }
+}
+
+fn amplitude_label(offset: usize, negative: bool, midpoint: usize, scale: AmplitudeScale) -> String {
+    if offset == 0 {
+        return match scale {
+            AmplitudeScale::Linear => "0.000".to_string(),
+            AmplitudeScale::Dbfs => "-inf dB".to_string(),
+        };
+    }
+
+    // a row `offset` away from the midpoint gets filled by samples that reach at least `offset + 1` rows.
+    let amplitude = rows_to_amplitude(offset + 1, midpoint);
+
+    match scale {
+        AmplitudeScale::Linear => format!("{}{:.3}", if negative { '-' } else { '+' }, amplitude),
+        AmplitudeScale::Dbfs => format!("{:.1} dB", 20.0 * amplitude.log10()),
+    }
+}
+
+fn title(info: &AudioInfo) -> String {
+    let name = info.name.clone().unwrap_or("<unnamed>".to_string());
+    let duration = format_time(info.duration, true);
+
+    match info.channels {
+        Some(channels) => format!("{} | duration: {} | {} Hz, {} channel(s)", name, duration, info.sample_rate, channels),
+        None => format!("{} | duration: {} | {} Hz", name, duration, info.sample_rate),
+    }
+}
+
+/// ## Info
+/// builds the bottom ruler line (with `|` at every tick) and the line with the tick labels under it.
+fn time_ruler(info: &AudioInfo, columns: usize) -> (String, String) {
+    let seconds_per_column = info.samples_per_column / info.sample_rate.max(1) as f64;
+
+    // the smallest interval that leaves room for the labels, or the biggest one if none does.
+    let (interval, millis) = TICK_INTERVALS
+        .iter()
+        .map(|&interval| (interval, interval < 1.0))
+        .find(|&(interval, millis)| {
+            let label_len = if millis { 9 } else { 5 };
+            interval / seconds_per_column >= (label_len + 2) as f64
+        })
+        .unwrap_or((TICK_INTERVALS[TICK_INTERVALS.len() - 1], false));
+
+    let mut ruler = vec!['-'; columns];
+    let mut labels = vec![' '; columns];
+    let mut free_from = 0;
+
+    let mut tick = (info.start / interval).ceil() as u64;
+    loop {
+        let time = tick as f64 * interval;
+        let column = ((time - info.start) / seconds_per_column).round() as usize;
+
+        if column >= columns {
+            break;
+        }
+
+        ruler[column] = '|';
+
+        let label = format_time(time, millis);
+        if column >= free_from && column + label.len() <= columns {
+            labels[column..column + label.len()].iter_mut().zip(label.chars()).for_each(|(cell, ch)| *cell = ch);
+            free_from = column + label.len() + 1;
+        }
+
+        tick += 1;
+    }
+
+    (
+        ruler.into_iter().collect(),
+        labels.into_iter().collect::<String>().trim_end().to_string(),
+    )
+}
+
+/// ## Info
+/// formats seconds as `mm:ss` (or `h:mm:ss` past the hour), with milliseconds (`mm:ss.mmm`) if `millis`.
+pub fn format_time(seconds: f64, millis: bool) -> String {
+    let total_millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    let (hours, minutes, secs, ms) = (
+        total_millis / 3_600_000,
+        (total_millis / 60_000) % 60,
+        (total_millis / 1000) % 60,
+        total_millis % 1000,
+    );
+
+    let time = if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{:02}:{:02}", minutes, secs)
+    };
+
+    if millis {
+        format!("{}.{:03}", time, ms)
+    } else {
+        time
+    }
+}
+
+#[test]
+fn test_annotated() {
+    let waveform = AsciiAudio(
+        FlatMatrix::new_fill(8, 40, ' '),
+        AudioInfo {
+            name: Some("beep.wav".to_string()),
+            sample_rate: 10,
+            channels: Some(1),
+            start: 0.0,
+            duration: 4.0,
+            samples_per_column: 1.0,
+        },
+    );
+
+    let plain = waveform.to_string();
+    let annotated = waveform.annotated(AmplitudeScale::Dbfs, &[]);
+    let lines = annotated.lines().collect::<Vec<_>>();
+
+    assert_eq!(plain.lines().count(), 8);
+    assert_eq!(lines.len(), 8 + 3);
+    assert_eq!(lines[0], "beep.wav | duration: 00:04.000 | 10 Hz, 1 channel(s)");
+    assert!(lines[9].trim_start().starts_with("+|"));
+    assert!(lines[10].trim_start().starts_with("00:00"));
+
+    // a level above full scale has nowhere to go, instead of being drawn on the top row.
+    assert_eq!(waveform.annotated(AmplitudeScale::Dbfs, &[3.0]), annotated);
+    assert_ne!(waveform.annotated(AmplitudeScale::Dbfs, &[0.0]), annotated);
+    assert_eq!(format_time(3723.5, true), "1:02:03.500");
+}
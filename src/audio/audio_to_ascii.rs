@@ -1,274 +1,534 @@
 use std::fmt::Display;
 
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
 use symphonia::{
     core::{
-        audio::Signal,
-        codecs::DecoderOptions,
-        formats::FormatOptions,
+        audio::{AudioBuffer, Signal},
+        codecs::{DecoderOptions, CODEC_TYPE_NULL},
+        formats::{FormatOptions, SeekMode, SeekTo, Track},
         io::{MediaSourceStream, MediaSourceStreamOptions},
-        meta::MetadataOptions,
+        meta::{MetadataOptions, Tag},
         probe::Hint,
+        units::Time,
     },
     default::{get_codecs, get_probe},
 };
 
+use super::source::{decode_raw, AudioSource, RawPcm};
 use crate::{
     core::{char::ColoredChar, flat_matrix::FlatMatrix}, report, utils::utils::Verbosity, Error, timestamp
 };
 
-pub struct AsciiAudio(pub FlatMatrix<char>);
+/// ## Info
+/// settings for how the audio input gets read, before it's turned into a waveform.
+///
+/// ## Args
+/// `start`, `end`: the time range to decode, in seconds; `None` means the start or end of the track.
+/// `format`: the container/codec hint (eg: mp3, ogg, wav); if `None`, the file extension is used.
+/// `track`: the index of the track to decode; if `None`, the first track with an audio codec is used.
+/// `raw`: if `Some`, the input has no container and is read as raw PCM as described; `format` and `track` are then ignored.
+#[derive(Debug, Clone, Default)]
+pub struct AudioOptions {
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    pub format: Option<String>,
+    pub track: Option<usize>,
+    pub raw: Option<RawPcm>,
+}
+
+/// ## Info
+/// what is known about the audio a waveform was made from; used to annotate the waveform.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioInfo {
+    /// the file name of the source, if it has one.
+    pub name: Option<String>,
+    pub sample_rate: u32,
+    pub channels: Option<usize>,
+    /// where the rendered range starts in the track, in seconds.
+    pub start: f64,
+    /// the length of the rendered range, in seconds.
+    pub duration: f64,
+    /// how many samples each column of the waveform stands for, on average.
+    pub samples_per_column: f64,
+}
+
+/// ## Info
+/// the number of columns a waveform gets when no width is asked for.
+pub const DEFAULT_WAVEFORM_WIDTH: usize = 4096;
+
+#[derive(Serialize, Deserialize)]
+pub struct AsciiAudio(pub FlatMatrix<char>, pub AudioInfo);
 
 impl AsciiAudio {
+    /// ## Info
+    /// turns the audio from `source` into an ASCII waveform `max_height * 2` characters tall.
+    ///
+    /// ## Args
+    /// `source`: a path (`&String`, `&str`), an in-memory file (`Vec<u8>`) or any other [`AudioSource`].
+    /// `width`: the number of columns of the waveform; each one shows the peaks of the samples it covers.
+    /// If `None`, every sample gets its own column, up to [`DEFAULT_WAVEFORM_WIDTH`] columns.
     pub fn new_parallel(
-        path: &String,
+        source: impl Into<AudioSource>,
+        max_height: usize,
+        width: Option<usize>,
+        uniform: bool,
+        invert: bool,
+        options: AudioOptions,
+        verbosity: Verbosity,
+    ) -> Result<Self, Error> {
+        let (columns, info) = reduce_columns(source.into(), width, &options, verbosity)?;
+        let midpoint = max_height.checked_sub(1).ok_or("the height of the waveform has to be at least 1")?;
+
+        report!(verbosity, @normal "starting general conversion algorithm...");
+        let shapes = columns
+            .into_par_iter()
+            .map(|peaks| ColumnShape::new(peaks, max_height, invert, uniform))
+            .collect::<Vec<_>>();
+
+        let mut matrix = FlatMatrix::new_fill(max_height * 2, shapes.len(), ' ');
+        matrix
+            .par_chunks_mut()
+            .enumerate()
+            .for_each(|(row, cells)| fill_row(row, midpoint, cells, &shapes));
+        report!(verbosity, @normal "finished general conversion algorithm");
+
+        Ok(Self(matrix, info))
+    }
+
+    /// ## Info
+    /// the sequential (not parallel) version of [`AsciiAudio::new_parallel`].
+    pub fn new_sequential(
+        source: impl Into<AudioSource>,
         max_height: usize,
+        width: Option<usize>,
         uniform: bool,
         invert: bool,
+        options: AudioOptions,
         verbosity: Verbosity,
     ) -> Result<Self, Error> {
-        let file = std::fs::File::open(path)?;
+        let (columns, info) = reduce_columns(source.into(), width, &options, verbosity)?;
+        let midpoint = max_height.checked_sub(1).ok_or("the height of the waveform has to be at least 1")?;
 
-        report!(verbosity, @verbose "probing for file and media type...");
-        let media_src_stream =
-            MediaSourceStream::new(Box::new(file), MediaSourceStreamOptions::default());
+        report!(verbosity, @normal "starting general conversion algorithm...");
+        let shapes = columns
+            .into_iter()
+            .map(|peaks| ColumnShape::new(peaks, max_height, invert, uniform))
+            .collect::<Vec<_>>();
+
+        let mut matrix = FlatMatrix::new_fill(max_height * 2, shapes.len(), ' ');
+        matrix
+            .chunks_mut()
+            .enumerate()
+            .for_each(|(row, cells)| fill_row(row, midpoint, cells, &shapes));
+        report!(verbosity, @normal "finished general conversion algorithm");
 
-        let hint = Hint::new();
-        // hint.with_extension(&media_type);
+        Ok(Self(matrix, info))
+    }
+}
 
-        let probed = get_probe().format(
-            &hint,
-            media_src_stream,
-            &FormatOptions::default(),
-            &MetadataOptions::default(),
-        )?;
-        let mut format = probed.format;
-        report!(verbosity, @verbose "finished probing for file and media type...");
+/// ## Info
+/// the loudest positive and negative sample of a waveform column.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct ColumnPeaks {
+    pub(crate) max: i16,
+    pub(crate) min: i16,
+}
 
+impl ColumnPeaks {
+    fn add(&mut self, sample: i16) {
+        self.max = self.max.max(sample);
+        self.min = self.min.min(sample);
+    }
 
-        report!(verbosity, @verbose "getting tracks and decoder...");
-        let track = format
-            .tracks()
-            .iter()
-            .next()
-            .ok_or("No supported audio tracks found")?;
-        let track_id = track.id;
-        let mut decoder = get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
-        report!(verbosity, @verbose "finished getting tracks and decoder");
-
-        // Storage for samples
-        let mut samples = Vec::new();
-
-        report!(verbosity, @verbose "decoding packets...");
-        // Decode packets and collect samples
-        while let Ok(packet) = format.next_packet() {
-            if packet.track_id() == track_id {
-                if let Ok(decoded) = decoder.decode(&packet) {
-                    match decoded {
-                        symphonia::core::audio::AudioBufferRef::S16(buf) => {
-                            samples.extend(buf.chan(0)); // Only take the first channel
-                        }
-                        symphonia::core::audio::AudioBufferRef::F32(buf) => {
-                            samples.extend(
-                                buf.chan(0)
-                                    .iter()
-                                    .map(|&sample| (sample * i16::MAX as f32) as i16),
-                            ); // Convert float to i16
-                        }
-                        _ => {
-                            eprintln!("Unsupported audio buffer format");
-                        }
-                    }
+    fn merge(self, other: ColumnPeaks) -> ColumnPeaks {
+        ColumnPeaks {
+            max: self.max.max(other.max),
+            min: self.min.min(other.min),
+        }
+    }
+}
+
+/// ## Info
+/// receives the samples streamed out by [`decode_samples`].
+pub(crate) trait SampleSink {
+    /// called once, before any sample, with the sample rate and (if the container tells) how many samples are coming.
+    fn prepare(&mut self, _sample_rate: u32, _expected: Option<u64>) {}
+
+    /// `first` is the index, from the start of the track, of the first of `samples`.
+    fn push(&mut self, first: u64, samples: &[i16]);
+}
+
+/// ## Info
+/// folds a stream of samples into waveform columns, keeping only the peaks of each column.
+///
+/// ## Note
+/// there's always a target width: without one, it's [`DEFAULT_WAVEFORM_WIDTH`]. If the number of samples is known upfront,
+/// each column covers an equal share of them; otherwise at most `2 * width` columns are ever kept: when that many are
+/// reached, neighbouring columns get merged and each new column starts covering twice as many samples. Either way the
+/// memory stays constant no matter how long the audio is.
+pub(crate) struct ColumnReducer {
+    width: usize,
+    samples_per_column: usize,
+    current: ColumnPeaks,
+    filled: usize,
+    columns: Vec<ColumnPeaks>,
+    total: u64,
+}
+
+impl ColumnReducer {
+    pub(crate) fn new(width: Option<usize>) -> Self {
+        let width = width.unwrap_or(DEFAULT_WAVEFORM_WIDTH).max(1);
+
+        ColumnReducer {
+            width,
+            samples_per_column: 1,
+            current: ColumnPeaks::default(),
+            filled: 0,
+            columns: Vec::with_capacity(width * 2),
+            total: 0,
+        }
+    }
+
+    fn push_samples(&mut self, samples: &[i16]) {
+        for &sample in samples {
+            self.current.add(sample);
+            self.filled += 1;
+            self.total += 1;
+
+            if self.filled == self.samples_per_column {
+                self.columns.push(self.current);
+                self.current = ColumnPeaks::default();
+                self.filled = 0;
+
+                if self.columns.len() >= self.width * 2 {
+                    self.columns = self.columns.chunks(2).map(|pair| pair[0].merge(pair[1])).collect();
+                    self.samples_per_column *= 2;
                 }
             }
         }
-        report!(verbosity, @verbose "finished decoding packets");
+    }
 
-        // println!("xx");
-        report!(verbosity, @normal "downscaling samples...");
-        let downscaled_samples: Vec<(u16, bool)> = samples
-            .into_par_iter()
-            .map(|sample| {
-                let sign = sample >= 0; // true if positive or 0
-                let magnitude = sample.abs() as u16;
-                (magnitude, sign)
-            })
-            .collect();
-        let height = max_height;
+    /// ## Info
+    /// returns the final columns (exactly `width` of them, if there are enough samples) and how many samples each one covers.
+    pub(crate) fn finish(mut self) -> (Vec<ColumnPeaks>, f64) {
+        if self.filled > 0 {
+            self.columns.push(self.current);
+        }
 
-        let midpoint = ((height * 2) - 1) / 2;
-        report!(verbosity, @normal "finished downscaling samples");
+        let width = self.width;
+        let columns = match self.columns.len() {
+            len if len > width => {
+                (0..width)
+                    .map(|column| {
+                        self.columns[column * len / width..(column + 1) * len / width]
+                            .iter()
+                            .fold(ColumnPeaks::default(), |acc, peaks| acc.merge(*peaks))
+                    })
+                    .collect()
+            }
+            _ => self.columns,
+        };
 
-        report!(verbosity, @normal "starting general conversion algorithm...");
-        let columns = downscaled_samples
-            .into_par_iter()
-            .map(|x| {
-                let x = (
-                    (((x.0 as f32 / 255.0) * midpoint as f32).round_ties_even() as u8),
-                    x.1,
-                );
-
-                // println!("{:?}", x);
-
-                let char_used = {
-                    let ch =
-                        ColoredChar::from_everything(x.0, (255, 255, 255), true, invert, uniform)
-                            .ch;
-
-                    if ch == ' ' {
-                        '.'
-                    } else {
-                        ch
-                    }
-                };
-
-                report!(verbosity, @verbose "got character! {}", char_used);
-
-                let mut column: Vec<char> = vec![' '; height as usize * 2];
-
-                if x.1 {
-                    for y in 0..x.0 {
-                        column[midpoint - y as usize] = char_used;
-                    }
-                } else {
-                    for y in 0..x.0 {
-                        column[midpoint + y as usize] = char_used;
-                    }
-                }
+        let samples_per_column = self.total as f64 / columns.len().max(1) as f64;
 
-                column
-            })
-            .collect::<FlatMatrix<_>>();
-        report!(verbosity, @normal "finished general conversion algorithm");
+        (columns, samples_per_column)
+    }
+}
 
-        let transposed = columns.transpose();
+impl SampleSink for ColumnReducer {
+    fn prepare(&mut self, _sample_rate: u32, expected: Option<u64>) {
+        if let Some(expected) = expected {
+            self.samples_per_column = (expected.div_ceil(self.width as u64) as usize).max(1);
+        }
+    }
 
-        Ok(Self(transposed))
+    fn push(&mut self, _first: u64, samples: &[i16]) {
+        self.push_samples(samples);
     }
+}
 
-    pub fn new_sequential(
-        path: &String,
-        // media_type: String,
-        max_height: usize,
-        uniform: bool,
-        invert: bool,
-        verbosity: Verbosity,
-    ) -> Result<Self, Error> {
-        let file = std::fs::File::open(path)?;
-
-        report!(verbosity, @verbose "probing for file and media type...");
-        let media_src_stream =
-            MediaSourceStream::new(Box::new(file), MediaSourceStreamOptions::default());
-
-        let hint = Hint::new();
-        // hint.with_extension(&media_type);
-
-        let probed = get_probe().format(
-            &hint,
-            media_src_stream,
-            &FormatOptions::default(),
-            &MetadataOptions::default(),
-        )?;
-        let mut format = probed.format;
-        report!(verbosity, @verbose "finished probing for file and media type...");
-
-        report!(verbosity, @verbose "getting tracks and decoder...");
-        let track = format
-            .tracks()
-            .iter()
-            .next()
-            .ok_or("No supported audio tracks found")?;
-        let track_id = track.id;
-        let mut decoder = get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
-        report!(verbosity, @verbose "getting tracks and decoder...");
-
-        // Storage for samples
-        let mut samples = Vec::new();
-
-        report!(verbosity, @verbose "decoding packets...");
-        // Decode packets and collect samples
-        while let Ok(packet) = format.next_packet() {
-            if packet.track_id() == track_id {
-                if let Ok(decoded) = decoder.decode(&packet) {
-                    match decoded {
-                        symphonia::core::audio::AudioBufferRef::S16(buf) => {
-                            samples.extend(buf.chan(0)); // Only take the first channel
-                        }
-                        symphonia::core::audio::AudioBufferRef::F32(buf) => {
-                            samples.extend(
-                                buf.chan(0)
-                                    .iter()
-                                    .map(|&sample| (sample * i16::MAX as f32) as i16),
-                            ); // Convert float to i16
-                        }
-                        _ => {
-                            eprintln!("Unsupported audio buffer format");
-                        }
-                    }
-                }
+/// ## Info
+/// decodes the audio from `source` straight into waveform columns.
+fn reduce_columns(
+    source: AudioSource,
+    width: Option<usize>,
+    options: &AudioOptions,
+    verbosity: Verbosity,
+) -> Result<(Vec<ColumnPeaks>, AudioInfo), Error> {
+    let mut reducer = ColumnReducer::new(width);
+    let mut info = decode_samples(source, options, verbosity, &mut reducer)?;
+
+    let (columns, samples_per_column) = reducer.finish();
+    info.samples_per_column = samples_per_column;
+
+    Ok((columns, info))
+}
+
+/// ## Info
+/// how a single waveform column gets drawn: how many characters it reaches above and below the midpoint, and which ones.
+struct ColumnShape {
+    up: usize,
+    up_char: char,
+    down: usize,
+    down_char: char,
+}
+
+impl ColumnShape {
+    fn new(peaks: ColumnPeaks, max_height: usize, invert: bool, uniform: bool) -> Self {
+        let up = peaks.max.unsigned_abs();
+        let down = peaks.min.unsigned_abs();
+
+        ColumnShape {
+            up: magnitude_to_rows(up, max_height - 1),
+            up_char: column_char(up, invert, uniform),
+            down: magnitude_to_rows(down, max_height - 1),
+            down_char: column_char(down, invert, uniform),
+        }
+    }
+}
+
+fn column_char(magnitude: u16, invert: bool, uniform: bool) -> char {
+    let density = (magnitude.min(i16::MAX as u16) as f32 / i16::MAX as f32 * 255.0).round_ties_even() as u8;
+    let ch = ColoredChar::from_everything(density, (255, 255, 255), true, invert, uniform).ch;
+
+    if ch == ' ' {
+        '.'
+    } else {
+        ch
+    }
+}
+
+/// ## Info
+/// draws the row `row` of the waveform directly, given the shape of every column.
+fn fill_row(row: usize, midpoint: usize, cells: &mut [char], shapes: &[ColumnShape]) {
+    for (cell, shape) in cells.iter_mut().zip(shapes) {
+        if row <= midpoint && midpoint - row < shape.up {
+            *cell = shape.up_char;
+        } else if row >= midpoint && row - midpoint < shape.down {
+            *cell = shape.down_char;
+        }
+    }
+}
+
+/// ## Info
+/// probes `source`, picks the track to decode according to `options`, and streams the samples of its first channel
+/// that fall in the requested time range to `sink`, one decoded packet at a time; nothing else is kept around.
+///
+/// ## Args
+/// `sink`: where the samples go; see [`SampleSink`].
+pub(crate) fn decode_samples(
+    source: AudioSource,
+    options: &AudioOptions,
+    verbosity: Verbosity,
+    sink: &mut impl SampleSink,
+) -> Result<AudioInfo, Error> {
+    if let (Some(start), Some(end)) = (options.start, options.end) {
+        if end <= start {
+            return Err("the end of the time range has to come after its start".into());
+        }
+    }
+
+    let name = source.name();
+    let extension = options.format.clone().or_else(|| source.extension());
+    let media = source.open()?;
+
+    if let Some(raw) = options.raw {
+        report!(verbosity, @verbose "reading raw audio...");
+        let start_frame = (options.start.unwrap_or(0.0) * raw.sample_rate as f64) as u64;
+        let end_frame = options.end.map(|end| (end * raw.sample_rate as f64) as u64);
+
+        let decoded_frames = decode_raw(media, raw, start_frame, end_frame, sink)?;
+        report!(verbosity, @verbose "finished reading raw audio");
+
+        if decoded_frames == 0 {
+            return Err("no audio samples were decoded in the requested time range".into());
+        }
+
+        return Ok(AudioInfo {
+            name,
+            sample_rate: raw.sample_rate,
+            channels: Some(raw.channels),
+            start: start_frame as f64 / raw.sample_rate as f64,
+            duration: decoded_frames as f64 / raw.sample_rate as f64,
+            samples_per_column: 1.0,
+        });
+    }
+
+    report!(verbosity, @verbose "probing for file and media type...");
+    let media_src_stream =
+        MediaSourceStream::new(media, MediaSourceStreamOptions::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = &extension {
+        hint.with_extension(extension);
+    }
+
+    let mut probed = get_probe().format(
+        &hint,
+        media_src_stream,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    report!(verbosity, @verbose "finished probing for file and media type...");
+
+    // tags found outside of the container (eg: ID3) come first, then the container's own.
+    let mut tags = probed
+        .metadata
+        .get()
+        .and_then(|mut metadata| metadata.skip_to_latest().map(|rev| rev.tags().to_vec()))
+        .unwrap_or_default();
+    let mut format = probed.format;
+    if let Some(rev) = format.metadata().current() {
+        tags.extend(rev.tags().iter().cloned());
+    }
+
+    report!(verbosity, @verbose "getting tracks and decoder...");
+    let track = select_track(format.tracks(), options.track)?;
+    let track_id = track.id;
+    let codec_params = track.codec_params.clone();
+    let mut decoder = get_codecs().make(&codec_params, &DecoderOptions::default())?;
+    report!(verbosity, @verbose "finished getting tracks and decoder");
+
+    let sample_rate = codec_params.sample_rate.ok_or("the selected track doesn't report a sample rate")?;
+    if let Verbosity::Verbose = verbosity {
+        report_metadata(track, &tags, verbosity);
+    }
+
+    if let Some(start) = options.start {
+        report!(verbosity, @verbose "seeking to {}s...", start);
+        let seeked = format.seek(
+            SeekMode::Accurate,
+            SeekTo::Time {
+                time: Time::from(start),
+                track_id: Some(track_id),
+            },
+        );
+
+        // sources that can't seek (eg: stdin) get decoded from the start, and the samples before `start` are dropped.
+        match seeked {
+            Ok(_) => {
+                decoder.reset();
+                report!(verbosity, @verbose "finished seeking");
+            }
+            Err(e) => {
+                report!(verbosity, @verbose "couldn't seek ({}), skipping to the start of the range instead", e);
             }
         }
-        report!(verbosity, @verbose "finished decoding packets");
+    }
 
-        report!(verbosity, @normal "downscaling samples...");
-        let downscaled_samples: Vec<(u16, bool)> = samples
-            .into_iter()
-            .map(|sample| {
-                let sign = sample >= 0; // true if positive or 0
-                let magnitude = sample.abs() as u16;
-                (magnitude, sign)
-            })
-            .collect();
-        let height = max_height;
+    // the range gets tracked in frames (samples of a single channel) from the beginning of the track.
+    let start_frame = (options.start.unwrap_or(0.0) * sample_rate as f64) as u64;
+    let end_frame = options.end.map(|end| (end * sample_rate as f64) as u64);
+
+    let expected = codec_params
+        .n_frames
+        .map(|n_frames| end_frame.map_or(n_frames, |end| end.min(n_frames)).saturating_sub(start_frame));
+    sink.prepare(sample_rate, expected);
+
+    let mut decoded_frames = 0_u64;
+
+    report!(verbosity, @verbose "decoding packets...");
+    // Decode packets and collect samples
+    while let Ok(packet) = format.next_packet() {
+        if packet.track_id() == track_id {
+            let packet_frame = match codec_params.time_base {
+                Some(time_base) => {
+                    let time = time_base.calc_time(packet.ts());
+                    ((time.seconds as f64 + time.frac) * sample_rate as f64) as u64
+                }
+                None => packet.ts(),
+            };
 
-        let midpoint = ((height * 2) - 1) / 2;
-        report!(verbosity, @normal "finished downscaling samples");
+            if end_frame.is_some_and(|end| packet_frame >= end) {
+                break;
+            }
 
-        report!(verbosity, @normal "starting general conversion algorithm...");
-        let columns = downscaled_samples
-            .into_iter()
-            .map(|x| {
-                let x = (
-                    (((x.0 as f32 / 255.0) * midpoint as f32).round_ties_even() as u8),
-                    x.1,
-                );
-
-                // println!("{:?}", x);
-
-                let char_used = {
-                    let ch =
-                        ColoredChar::from_everything(x.0, (255, 255, 255), true, invert, uniform)
-                            .ch;
-
-                    if ch == ' ' {
-                        '.'
-                    } else {
-                        ch
-                    }
-                };
-
-                report!(verbosity, @verbose "got character! {}", char_used);
-
-                let mut column: Vec<char> = vec![' '; height as usize * 2];
-
-                if x.1 {
-                    for y in 0..x.0 {
-                        column[midpoint - y as usize] = char_used;
-                    }
-                } else {
-                    for y in 0..x.0 {
-                        column[midpoint + y as usize] = char_used;
-                    }
+            if let Ok(decoded) = decoder.decode(&packet) {
+                // every sample format (eg: the S24 and S32 of WAV and FLAC) gets converted to i16, and only the first channel is kept.
+                let mut converted = AudioBuffer::<i16>::new(decoded.capacity() as u64, *decoded.spec());
+                decoded.convert(&mut converted);
+                let chunk = converted.chan(0);
+
+                // only the part of the packet inside of the time range is kept.
+                let packet_end = packet_frame + chunk.len() as u64;
+                let from = start_frame.clamp(packet_frame, packet_end);
+                let to = end_frame.unwrap_or(packet_end).clamp(from, packet_end);
+
+                if to > from {
+                    sink.push(from, &chunk[(from - packet_frame) as usize..(to - packet_frame) as usize]);
+                    decoded_frames += to - from;
                 }
+            }
+        }
+    }
+    report!(verbosity, @verbose "finished decoding packets");
 
-                column
-            })
-            .collect::<FlatMatrix<_>>();
-        report!(verbosity, @normal "finished general conversion algorithm");
+    if decoded_frames == 0 {
+        return Err("no audio samples were decoded in the requested time range".into());
+    }
+
+    let info = AudioInfo {
+        name,
+        sample_rate,
+        channels: codec_params.channels.map(|channels| channels.count()),
+        start: start_frame as f64 / sample_rate as f64,
+        duration: decoded_frames as f64 / sample_rate as f64,
+        samples_per_column: 1.0,
+    };
 
-        let transposed = columns.transpose();
+    Ok(info)
+}
+
+/// ## Info
+/// how many characters (from the midpoint, included) a sample of the given magnitude gets in its waveform column;
+/// a full scale sample reaches the edge of the waveform.
+pub(crate) fn magnitude_to_rows(magnitude: u16, midpoint: usize) -> usize {
+    let amplitude = magnitude.min(i16::MAX as u16) as f32 / i16::MAX as f32;
 
-        Ok(Self(transposed))
+    (amplitude * (midpoint + 1) as f32).round_ties_even() as usize
+}
+
+/// ## Info
+/// the inverse of [`magnitude_to_rows`]: the amplitude (as a fraction of full scale) that reaches `rows` characters from the midpoint.
+pub(crate) fn rows_to_amplitude(rows: usize, midpoint: usize) -> f64 {
+    rows as f64 / (midpoint + 1) as f64
+}
+
+/// ## Info
+/// picks the track at `index` if given, otherwise the first one with an audio codec.
+pub(crate) fn select_track(tracks: &[Track], index: Option<usize>) -> Result<&Track, Error> {
+    let track = match index {
+        Some(index) => tracks.get(index).ok_or("no track exists at the requested index")?,
+        None => tracks
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL && track.codec_params.sample_rate.is_some())
+            .ok_or("No supported audio tracks found")?,
+    };
+
+    Ok(track)
+}
+
+fn report_metadata(track: &Track, tags: &[Tag], verbosity: Verbosity) {
+    let params = &track.codec_params;
+
+    let track_id = track.id;
+    let sample_rate = params.sample_rate.map(|rate| format!("{} Hz", rate)).unwrap_or("unknown".to_string());
+    let channels = params.channels.map(|channels| channels.count().to_string()).unwrap_or("unknown".to_string());
+    let duration = match (params.n_frames, params.time_base) {
+        (Some(n_frames), Some(time_base)) => {
+            let time = time_base.calc_time(n_frames);
+            format!("{:.3}s", time.seconds as f64 + time.frac)
+        }
+        _ => "unknown".to_string(),
+    };
+
+    report!(verbosity, @verbose "track id: {}, sample rate: {}, channels: {}, duration: {}", track_id, sample_rate, channels, duration);
+
+    for tag in tags {
+        let key = &tag.key;
+        let value = &tag.value;
+        report!(verbosity, @verbose "tag: {} = {}", key, value);
     }
 }
 
@@ -293,13 +553,66 @@ impl Display for AsciiAudio {
 #[test]
 fn test() {
     let ascii_wave = AsciiAudio::new_sequential(
-        &"picts/beep-sound-short-237619.mp3".to_string(),
+        "picts/beep-sound-short-237619.mp3",
         255,
+        None,
         false,
         false,
+        AudioOptions::default(),
         Verbosity::Normal
     )
     .unwrap();
 
     println!("{}", ascii_wave)
 } // ......
+
+#[test]
+fn test_column_reducer() {
+    let samples = (0..1000).map(|index| if index % 2 == 0 { index as i16 } else { -(index as i16) }).collect::<Vec<_>>();
+
+    // with a known length, every column covers exactly the same samples.
+    let mut reducer = ColumnReducer::new(Some(10));
+    reducer.prepare(44100, Some(1000));
+    samples.chunks(7).for_each(|chunk| reducer.push(0, chunk));
+    let (columns, samples_per_column) = reducer.finish();
+
+    assert_eq!(columns.len(), 10);
+    assert_eq!(samples_per_column, 100.0);
+    assert_eq!(columns[0], ColumnPeaks { max: 98, min: -99 });
+    assert_eq!(columns[9], ColumnPeaks { max: 998, min: -999 });
+
+    // without it, columns get merged as they come, but the width is still respected.
+    let mut reducer = ColumnReducer::new(Some(10));
+    samples.chunks(7).for_each(|chunk| reducer.push(0, chunk));
+    let (columns, _) = reducer.finish();
+
+    assert_eq!(columns.len(), 10);
+    assert_eq!(columns[9].min, -999);
+
+    let mut reducer = ColumnReducer::new(None);
+    reducer.push(0, &samples[..5]);
+    assert_eq!(reducer.finish().0.len(), 5);
+}
+
+#[test]
+fn test_column_reducer_bounded_without_width() {
+    let chunk = (0..4096).map(|index| (index % 200) as i16 - 100).collect::<Vec<_>>();
+
+    // ten minutes of 48 kHz audio, with and without its length known upfront.
+    for expected in [Some(48_000 * 600), None] {
+        let mut reducer = ColumnReducer::new(None);
+        reducer.prepare(48_000, expected);
+
+        let mut pushed = 0_u64;
+        while pushed < 48_000 * 600 {
+            reducer.push(pushed, &chunk);
+            pushed += chunk.len() as u64;
+
+            assert!(reducer.columns.len() <= DEFAULT_WAVEFORM_WIDTH * 2);
+        }
+
+        let (columns, _) = reducer.finish();
+        assert_eq!(columns.len(), DEFAULT_WAVEFORM_WIDTH);
+        assert_eq!(columns[0], ColumnPeaks { max: 99, min: -100 });
+    }
+}
@@ -1 +1,3 @@
-pub mod audio_to_ascii;
\ No newline at end of file
+pub mod audio_to_ascii;
+pub mod annotate;
+pub mod source;
\ No newline at end of file
@@ -0,0 +1,208 @@
+use std::{
+    fs::File,
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use clap::ValueEnum;
+use symphonia::core::io::{MediaSource, ReadOnlySource};
+
+use super::audio_to_ascii::SampleSink;
+use crate::Error;
+
+/// ## Info
+/// where the audio to turn into a waveform comes from.
+pub enum AudioSource {
+    /// a file on disk; its extension is used as a format hint.
+    Path(String),
+    /// an already opened source, eg: an in-memory `Cursor<Vec<u8>>`.
+    Media(Box<dyn MediaSource>),
+    /// a source that can only be read forward, eg: stdin.
+    Stream(Box<dyn Read + Send + Sync>),
+}
+
+impl AudioSource {
+    /// ## Info
+    /// reads the audio from stdin.
+    pub fn stdin() -> Self {
+        Self::Stream(Box::new(std::io::stdin()))
+    }
+
+    /// ## Info
+    /// the file name of the source, if it has one.
+    pub fn name(&self) -> Option<String> {
+        match self {
+            Self::Path(path) => Path::new(path).file_name().map(|name| name.to_string_lossy().to_string()),
+            Self::Media(_) | Self::Stream(_) => None,
+        }
+    }
+
+    pub(crate) fn extension(&self) -> Option<String> {
+        match self {
+            Self::Path(path) => Path::new(path).extension().map(|ext| ext.to_string_lossy().to_string()),
+            Self::Media(_) | Self::Stream(_) => None,
+        }
+    }
+
+    pub(crate) fn open(self) -> Result<Box<dyn MediaSource>, Error> {
+        Ok(match self {
+            Self::Path(path) => Box::new(File::open(path)?),
+            Self::Media(media) => media,
+            Self::Stream(reader) => Box::new(ReadOnlySource::new(reader)),
+        })
+    }
+}
+
+impl From<&String> for AudioSource {
+    fn from(value: &String) -> Self {
+        Self::Path(value.clone())
+    }
+}
+
+impl From<&str> for AudioSource {
+    fn from(value: &str) -> Self {
+        Self::Path(value.to_string())
+    }
+}
+
+impl From<Vec<u8>> for AudioSource {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Media(Box::new(Cursor::new(value)))
+    }
+}
+
+/// ## Info
+/// the sample encoding of raw PCM audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RawFormat {
+    /// unsigned 8-bit.
+    #[value(name = "u8")]
+    U8,
+    /// signed 16-bit, little endian.
+    #[value(name = "s16le")]
+    S16Le,
+    /// signed 16-bit, big endian.
+    #[value(name = "s16be")]
+    S16Be,
+    /// signed 32-bit, little endian.
+    #[value(name = "s32le")]
+    S32Le,
+    /// 32-bit float, little endian.
+    #[value(name = "f32le")]
+    F32Le,
+    /// 32-bit float, big endian.
+    #[value(name = "f32be")]
+    F32Be,
+}
+
+impl RawFormat {
+    pub fn bytes_per_sample(&self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::S16Le | Self::S16Be => 2,
+            Self::S32Le | Self::F32Le | Self::F32Be => 4,
+        }
+    }
+
+    fn to_i16(self, bytes: &[u8]) -> i16 {
+        match self {
+            Self::U8 => ((bytes[0] as i16) - 128) << 8,
+            Self::S16Le => i16::from_le_bytes([bytes[0], bytes[1]]),
+            Self::S16Be => i16::from_be_bytes([bytes[0], bytes[1]]),
+            Self::S32Le => (i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) >> 16) as i16,
+            Self::F32Le => (f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) * i16::MAX as f32) as i16,
+            Self::F32Be => (f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) * i16::MAX as f32) as i16,
+        }
+    }
+}
+
+/// ## Info
+/// describes audio without a container: just interleaved samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RawPcm {
+    pub format: RawFormat,
+    pub sample_rate: u32,
+    pub channels: usize,
+}
+
+/// how many frames get read from a raw source at once.
+const RAW_CHUNK_FRAMES: usize = 4096;
+
+/// ## Info
+/// reads raw interleaved PCM from `media` and streams the first channel of the frames in `start_frame..end_frame` to `sink`.
+/// Returns the number of frames given to `sink`.
+pub(crate) fn decode_raw(
+    mut media: Box<dyn MediaSource>,
+    raw: RawPcm,
+    start_frame: u64,
+    end_frame: Option<u64>,
+    sink: &mut impl SampleSink,
+) -> Result<u64, Error> {
+    if raw.channels == 0 || raw.sample_rate == 0 {
+        return Err("raw audio needs at least one channel and a sample rate above 0".into());
+    }
+
+    let frame_size = raw.format.bytes_per_sample() * raw.channels;
+    let expected = media
+        .byte_len()
+        .map(|len| end_frame.map_or(len / frame_size as u64, |end| end.min(len / frame_size as u64)).saturating_sub(start_frame));
+    sink.prepare(raw.sample_rate, expected);
+
+    let mut buffer = vec![0_u8; frame_size * RAW_CHUNK_FRAMES];
+    let mut samples = Vec::with_capacity(RAW_CHUNK_FRAMES);
+    let mut frame_index = 0_u64;
+    let mut decoded = 0_u64;
+    let mut filled = 0;
+
+    loop {
+        let read = media.read(&mut buffer[filled..])?;
+        filled += read;
+
+        // a partial frame waits for the next read, unless the source is over.
+        let whole = filled / frame_size;
+        if whole == 0 && read != 0 {
+            continue;
+        }
+
+        samples.clear();
+        samples.extend(buffer[..whole * frame_size].chunks_exact(frame_size).map(|frame| raw.format.to_i16(frame)));
+
+        let from = start_frame.clamp(frame_index, frame_index + whole as u64);
+        let to = end_frame.unwrap_or(u64::MAX).clamp(from, frame_index + whole as u64);
+        if to > from {
+            sink.push(from, &samples[(from - frame_index) as usize..(to - frame_index) as usize]);
+            decoded += to - from;
+        }
+
+        frame_index += whole as u64;
+        buffer.copy_within(whole * frame_size..filled, 0);
+        filled -= whole * frame_size;
+
+        if read == 0 || end_frame.is_some_and(|end| frame_index >= end) {
+            break;
+        }
+    }
+
+    Ok(decoded)
+}
+
+#[test]
+fn test_decode_raw() {
+    struct Collect(Vec<(u64, i16)>);
+
+    impl SampleSink for Collect {
+        fn push(&mut self, first: u64, samples: &[i16]) {
+            self.0.extend(samples.iter().enumerate().map(|(index, sample)| (first + index as u64, *sample)));
+        }
+    }
+
+    // 2 channels of s16le: the first channel counts up, the second one is noise.
+    let bytes = (0..10_i16).flat_map(|frame| [frame.to_le_bytes(), (-1_i16).to_le_bytes()].concat()).collect::<Vec<_>>();
+    let raw = RawPcm { format: RawFormat::S16Le, sample_rate: 8000, channels: 2 };
+
+    let mut sink = Collect(vec![]);
+    let decoded = decode_raw(Box::new(Cursor::new(bytes)), raw, 3, Some(7), &mut sink).unwrap();
+
+    assert_eq!(decoded, 4);
+    assert_eq!(sink.0, vec![(3, 3), (4, 4), (5, 5), (6, 6)]);
+}
@@ -0,0 +1,356 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+    thread,
+};
+
+use clap::ValueEnum;
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use xxhash_rust::xxh3::xxh3_128;
+
+use crate::{
+    core::{
+        export::{ExportFormat, GridExport},
+        render::{OutputFormat, RenderOptions},
+    },
+    image::{decode::decode_image_bytes, image_to_ascii::AsciiImg},
+    report, timestamp,
+    utils::utils::Verbosity,
+    Error,
+};
+
+/// where the hashes of the converted inputs get kept, in the output directory, for [`SkipPolicy::Hash`].
+const MANIFEST: &str = ".anything_to_ascii_batch.json";
+
+/// ## Info
+/// which inputs a batch leaves alone because their output is already up to date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum SkipPolicy {
+    /// convert every input again.
+    Never,
+    /// skip inputs whose output was written after the input was last changed; changing the options doesn't count.
+    #[default]
+    Mtime,
+    /// skip inputs whose contents and options are the same as when their output was written.
+    Hash,
+}
+
+/// ## Info
+/// how every image of a batch gets converted and written.
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    pub height: Option<usize>,
+    pub width: Option<usize>,
+    pub invert: bool,
+    pub colored: bool,
+    pub uniform: bool,
+    pub format: OutputFormat,
+    pub background: Option<(u8, u8, u8)>,
+    /// writes the character grid instead, ignoring `format` and `background`.
+    pub export: Option<ExportFormat>,
+    pub skip: SkipPolicy,
+    /// how many images get converted at the same time; 0 means one per CPU.
+    pub jobs: usize,
+}
+
+impl BatchOptions {
+    /// ## Info
+    /// the extension of the files written, added after the one of the input, eg: `shoe.png.txt`.
+    fn extension(&self) -> &'static str {
+        match (self.export, self.format) {
+            (Some(ExportFormat::Json), _) => "json",
+            (Some(ExportFormat::Msgpack), _) => "msgpack",
+            (None, OutputFormat::Text) => "txt",
+            (None, OutputFormat::Html) => "html",
+            (None, OutputFormat::Svg) => "svg",
+        }
+    }
+
+    /// ## Info
+    /// every option that changes the output, to hash along with the input.
+    fn fingerprint(&self) -> String {
+        format!(
+            "{:?}:{:?}:{}:{}:{}:{:?}:{:?}:{:?}",
+            self.height, self.width, self.invert, self.colored, self.uniform, self.format, self.background, self.export,
+        )
+    }
+}
+
+/// ## Info
+/// what happened to the inputs of a batch, each given by its path.
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub converted: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+    /// with the reason.
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+enum Outcome {
+    Converted(Option<String>),
+    Skipped,
+    Failed(String),
+}
+
+/// ## Info
+/// converts every image found in `inputs` and writes it in `output`, mirroring the directories the images are in.
+/// A file that fails doesn't stop the others; its error ends up in the report.
+///
+/// ## Args
+/// `inputs`: image files, directories (searched recursively for images) or glob patterns (eg: `photos/**/*.jpg`).
+/// The path of an output, relative to `output`, is the one of the image relative to the directory it was found in (or
+/// to the part of the pattern before the first wildcard); see [`collect_inputs`].
+pub fn run_batch(inputs: &[String], output: &Path, options: &BatchOptions, verbosity: Verbosity) -> Result<BatchReport, Error> {
+    let files = collect_inputs(inputs)?;
+    report!(verbosity, @normal "found {} images to convert", (files.len()));
+
+    fs::create_dir_all(output)?;
+    let manifest_path = output.join(MANIFEST);
+    let manifest = Mutex::new(match options.skip {
+        SkipPolicy::Hash => fs::read(&manifest_path)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<BTreeMap<String, String>>(&json).ok())
+            .unwrap_or_default(),
+        _ => BTreeMap::new(),
+    });
+
+    let jobs = match options.jobs {
+        0 => thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1),
+        jobs => jobs,
+    };
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .map_err(|_| Error::InternalError("couldn't start the threads of the batch"))?;
+
+    let progress = match verbosity {
+        Verbosity::Normal => {
+            let progress = ProgressBar::new(files.len() as u64);
+            progress.set_style(
+                ProgressStyle::default_bar()
+                    .template("batch progress: [{bar:40.red/pink}] {pos:>3}/{len}")
+                    .unwrap(),
+            );
+            Some(progress)
+        }
+        _ => None,
+    };
+
+    let outcomes = pool.install(|| {
+        files
+            .par_iter()
+            .map(|(source, relative)| {
+                let key = source.to_string_lossy().to_string();
+                let mut target = output.join(relative).into_os_string();
+                target.push(".");
+                target.push(options.extension());
+
+                let known = manifest.lock().unwrap().get(&key).cloned();
+                let outcome = convert_file(source, Path::new(&target), options, known);
+
+                if let Some(progress) = &progress {
+                    if let Outcome::Failed(error) = &outcome {
+                        progress.suspend(|| {
+                            report!(verbosity, @normal "couldn't convert {}: {}", (source.display()), error);
+                        });
+                    }
+                    progress.inc(1);
+                }
+                if let Outcome::Converted(Some(hash)) = &outcome {
+                    manifest.lock().unwrap().insert(key, hash.clone());
+                }
+
+                (source.clone(), outcome)
+            })
+            .collect::<Vec<_>>()
+    });
+
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+
+    if options.skip == SkipPolicy::Hash {
+        fs::write(&manifest_path, serde_json::to_vec_pretty(&*manifest.lock().unwrap())?)?;
+    }
+
+    let mut report = BatchReport::default();
+    for (source, outcome) in outcomes {
+        match outcome {
+            Outcome::Converted(_) => report.converted.push(source),
+            Outcome::Skipped => report.skipped.push(source),
+            Outcome::Failed(error) => report.failed.push((source, error)),
+        }
+    }
+
+    Ok(report)
+}
+
+/// ## Info
+/// converts a single image, unless its output is up to date; `known` is the hash it had the last time, if any.
+/// A conversion gives back the new hash when hashes are in use.
+fn convert_file(source: &Path, target: &Path, options: &BatchOptions, known: Option<String>) -> Outcome {
+    let up_to_date = |target: &Path| -> Result<bool, Error> {
+        let (source, target) = (fs::metadata(source)?, fs::metadata(target)?);
+        Ok(target.modified()? >= source.modified()?)
+    };
+    if options.skip == SkipPolicy::Mtime && up_to_date(target).unwrap_or(false) {
+        return Outcome::Skipped;
+    }
+
+    let bytes = match fs::read(source) {
+        Ok(bytes) => bytes,
+        Err(e) => return Outcome::Failed(Error::from(e).to_string()),
+    };
+
+    let hash = (options.skip == SkipPolicy::Hash)
+        .then(|| format!("{:032x}", xxh3_128(format!("{:032x}:{}", xxh3_128(&bytes), options.fingerprint()).as_bytes())));
+    if hash.is_some() && hash == known && target.exists() {
+        return Outcome::Skipped;
+    }
+
+    let extension = source.extension().map(|ext| ext.to_string_lossy());
+    match convert_bytes(&bytes, extension.as_deref(), options).and_then(|contents| write_output(target, &contents)) {
+        Ok(()) => Outcome::Converted(hash),
+        Err(e) => Outcome::Failed(e.to_string()),
+    }
+}
+
+fn convert_bytes(bytes: &[u8], extension: Option<&str>, options: &BatchOptions) -> Result<Vec<u8>, Error> {
+    let image = decode_image_bytes(bytes, extension)?;
+
+    // the files are already converted in parallel, so every one of them uses a single thread.
+    let ascii = AsciiImg::new_sequential(image, options.height, options.width, options.invert, !options.colored, options.uniform, Verbosity::Silent)?;
+
+    if let Some(export) = options.export {
+        return GridExport::from(&ascii).to_bytes(export);
+    }
+
+    let render = RenderOptions { background: options.background, ..RenderOptions::default() };
+    Ok(match options.format {
+        OutputFormat::Text => ascii.to_string(),
+        OutputFormat::Html => ascii.to_html(&render),
+        OutputFormat::Svg => ascii.to_svg(&render),
+    }
+    .into_bytes())
+}
+
+fn write_output(target: &Path, contents: &[u8]) -> Result<(), Error> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    Ok(fs::write(target, contents)?)
+}
+
+/// ## Info
+/// every file `inputs` stands for, with the path its output gets (relative to the output directory, without the
+/// added extension), sorted and without duplicates.
+///
+/// ## Note
+/// when different files would get the same output (eg: `a/x.png` and `b/x.png` from the inputs `a/` and `b/`), every
+/// output goes under the name of the directory it was found in instead (`a/x.png` and `b/x.png`, but also `a/y.png`),
+/// so that the outputs of one directory stay together. If they still clash, the batch fails.
+pub fn collect_inputs(inputs: &[String]) -> Result<Vec<(PathBuf, PathBuf)>, Error> {
+    // every file, with its path relative to the directory it was found in, and that directory.
+    let mut files = BTreeMap::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+
+        if path.is_dir() {
+            let pattern = format!("{}/**/*", glob::Pattern::escape(input.trim_end_matches('/')));
+            for found in glob::glob(&pattern)?.flatten() {
+                // directories can hold anything, so only the files that look like images are taken.
+                if found.is_file() && image::ImageFormat::from_path(&found).is_ok() {
+                    let relative = found.strip_prefix(path).unwrap_or(&found).to_path_buf();
+                    files.entry(found).or_insert((relative, path.to_path_buf()));
+                }
+            }
+        } else if path.is_file() {
+            let name = path.file_name().map(PathBuf::from).unwrap_or_else(|| path.to_path_buf());
+            let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            files.entry(path.to_path_buf()).or_insert((name, parent));
+        } else {
+            let base = glob_base(input);
+            for found in glob::glob(input)?.flatten().filter(|found| found.is_file()) {
+                let relative = found.strip_prefix(&base).unwrap_or(&found).to_path_buf();
+                files.entry(found).or_insert((relative, base.clone()));
+            }
+        }
+    }
+
+    // an output can't end up outside of the output directory.
+    let files = files
+        .into_iter()
+        .map(|(source, (relative, root))| {
+            let relative = relative.components().filter(|part| matches!(part, Component::Normal(_))).collect::<PathBuf>();
+            (source, relative, root)
+        })
+        .collect::<Vec<_>>();
+
+    let mut clashes = BTreeMap::<&Path, usize>::new();
+    for (_, relative, _) in &files {
+        *clashes.entry(relative.as_path()).or_default() += 1;
+    }
+    let clash = clashes.values().any(|&count| count > 1);
+
+    let files = files
+        .iter()
+        .map(|(source, relative, root)| match clash {
+            false => (source.clone(), relative.clone()),
+            true => {
+                let root = if root.as_os_str().is_empty() { Path::new(".") } else { root.as_path() };
+                let name = root
+                    .canonicalize()
+                    .ok()
+                    .and_then(|root| root.file_name().map(PathBuf::from))
+                    .unwrap_or_default();
+                (source.clone(), name.join(relative))
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut outputs = files.iter().map(|(_, relative)| relative).collect::<Vec<_>>();
+    outputs.sort();
+    if outputs.windows(2).any(|pair| pair[0] == pair[1]) {
+        return Err("different inputs would be written to the same output, even under the name of the directory they were found in".into());
+    }
+
+    Ok(files)
+}
+
+/// ## Info
+/// the directories at the start of a glob pattern, up to the first one with a wildcard.
+fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|part| !part.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+#[test]
+fn test_collect_inputs() {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    fs::create_dir_all(dir.join("shoes/red")).unwrap();
+    for file in ["shoes/a.png", "shoes/red/b.jpg", "shoes/notes.txt", "c.png"] {
+        fs::write(dir.join(file), b"").unwrap();
+    }
+    let root = dir.to_string_lossy().to_string();
+
+    let found = collect_inputs(&[format!("{}/shoes", root), format!("{}/*.png", root)]).unwrap();
+    let relative = found.iter().map(|(_, relative)| relative.clone()).collect::<Vec<_>>();
+    assert_eq!(relative, vec![PathBuf::from("c.png"), PathBuf::from("a.png"), PathBuf::from("red/b.jpg")]);
+
+    // the same name from two places puts every output under the name of its place.
+    fs::write(dir.join("shoes/c.png"), b"").unwrap();
+    let found = collect_inputs(&[format!("{}/shoes", root), format!("{}/c.png", root)]).unwrap();
+    let relative = found.iter().map(|(_, relative)| relative.clone()).collect::<Vec<_>>();
+    let name = dir.canonicalize().unwrap().file_name().map(PathBuf::from).unwrap();
+    assert_eq!(relative, vec![name.join("c.png"), PathBuf::from("shoes/a.png"), PathBuf::from("shoes/c.png"), PathBuf::from("shoes/red/b.jpg")]);
+
+    assert_eq!(glob_base("photos/2024/**/*.jpg"), PathBuf::from("photos/2024"));
+}
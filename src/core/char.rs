@@ -1,15 +1,27 @@
 use colored::{Color, Colorize, CustomColor};
 use image::Rgba;
+use serde::{Deserialize, Serialize};
 
 /// ## Info the character selector and the one that prints the ANSI colors if `colored`.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ColoredChar {
+    #[serde(with = "CustomColorDef")]
     pub color: CustomColor,
     pub ch: char,
     pub density: u8,
     pub display: bool,
 }
 
+/// ## Info
+/// (de)serializes the `CustomColor` of a [`ColoredChar`], which doesn't implement `serde` itself.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "CustomColor")]
+struct CustomColorDef {
+    r: u8,
+    g: u8,
+    b: u8,
+}
+
 impl ColoredChar {
     pub const CHAR_EMPTY: char = ' ';
     pub const CHAR0_17: char = '.';
@@ -180,6 +192,33 @@ impl ColoredChar {
         }
     }
 
+    /// ## Info
+    /// the middle of the density range `ch` gets picked for (without `invert`), or `None` if it isn't one of the
+    /// characters above.
+    pub fn density_of(ch: char) -> Option<u8> {
+        let density = match ch {
+            Self::CHAR_EMPTY => 0,
+            Self::CHAR0_17 => 9,
+            Self::CHAR17_34 => 25,
+            Self::CHAR34_51 => 42,
+            Self::CHAR51_68 => 59,
+            Self::CHAR68_85 => 76,
+            Self::CHAR85_102 => 93,
+            Self::CHAR102_119 => 110,
+            Self::CHAR119_136 => 127,
+            Self::CHAR136_153 => 144,
+            Self::CHAR153_170 => 161,
+            Self::CHAR170_187 => 178,
+            Self::CHAR187_204 => 195,
+            Self::CHAR204_221 => 212,
+            Self::CHAR221_238 => 229,
+            Self::CHAR238_255 => 246,
+            _ => return None,
+        };
+
+        Some(density)
+    }
+
     pub fn is_grayscale(&self) -> bool {
         self.color.r == self.color.g && self.color.g == self.color.b
     }
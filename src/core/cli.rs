@@ -1,5 +1,13 @@
 use clap::{Parser, Subcommand};
 
+use crate::{
+    audio::{annotate::AmplitudeScale, source::RawFormat},
+    batch::batch::SkipPolicy,
+    core::{export::ExportFormat, render::OutputFormat},
+    utils::utils::{parse_color, parse_timestamp},
+    video::audio_strip::AudioStrip,
+};
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Cli {
@@ -16,13 +24,85 @@ pub struct Cli {
 #[derive(Subcommand, Clone, Debug)]
 pub enum Commands {
     /// Launches the web api.
+    ///
+    /// Settings can also come from a TOML file (`Rocket.toml` by default) and `ROCKET_` environment variables, eg:
+    /// `ROCKET_MAX_CONVERSIONS=4`; flags take priority over both.
     Api {
-        /// prevents the api calls to run any parallel processes.
-        #[arg(long = "no-parallel")]
-        no_parallel: bool,
+        /// the TOML file to read the settings from; defaults to `Rocket.toml` (or the `ROCKET_CONFIG` variable).
+        #[arg(short, long)]
+        config: Option<String>,
+
+        /// the IP address to listen on; defaults to 127.0.0.1.
+        #[arg(short, long)]
+        address: Option<String>,
 
         #[arg(short, long)]
         port: Option<u16>,
+
+        /// the number of threads handling requests.
+        #[arg(long)]
+        workers: Option<usize>,
+
+        /// makes the api calls sequential unless a request asks for `parallel=true`.
+        #[arg(long = "no-parallel")]
+        no_parallel: bool,
+
+        /// the most threads a single parallel conversion can use; defaults to one per CPU.
+        #[arg(long = "max-threads")]
+        max_threads: Option<usize>,
+
+        /// how many conversions (including queued jobs and streams) can run at the same time; defaults to 2.
+        // `--jobs` is what this used to be called, when it only counted jobs.
+        #[arg(long = "max-conversions", alias = "jobs")]
+        max_conversions: Option<usize>,
+
+        /// how many seconds a request can spend converting before giving up; 0 means no limit; defaults to 300.
+        #[arg(long)]
+        timeout: Option<u64>,
+
+        /// an origin allowed to call the api from a browser, eg: `https://example.com`, or `*` for any; can be repeated.
+        #[arg(long = "cors-origin", value_delimiter = ',')]
+        cors_origins: Option<Vec<String>>,
+
+        /// the certificate chain to serve HTTPS with, in PEM format.
+        #[arg(long = "tls-cert", requires = "tls_key")]
+        tls_cert: Option<String>,
+
+        /// the private key of the certificate, in PEM format.
+        #[arg(long = "tls-key", requires = "tls_cert")]
+        tls_key: Option<String>,
+
+        /// the biggest image that can be uploaded, eg: `20 MiB`; defaults to 10 MB.
+        #[arg(long = "image-limit")]
+        image_limit: Option<String>,
+
+        /// the biggest video that can be uploaded, eg: `1 GiB`; defaults to 100 MB.
+        #[arg(long = "video-limit")]
+        video_limit: Option<String>,
+
+        /// the biggest audio file that can be uploaded; defaults to 100 MB.
+        #[arg(long = "audio-limit")]
+        audio_limit: Option<String>,
+
+        /// how many megabytes of conversion results to keep cached; 0 turns the cache off; defaults to 64.
+        #[arg(long = "cache-size")]
+        cache_size: Option<usize>,
+
+        /// a directory to also keep cached results in, so they survive restarts.
+        #[arg(long = "cache-dir")]
+        cache_dir: Option<String>,
+
+        /// a TOML file with the API keys allowed to call the api, and their limits; reloaded whenever it changes.
+        #[arg(long = "api-keys-file")]
+        api_keys_file: Option<String>,
+
+        /// how many requests an API key can make per minute; 0 means no limit, the default.
+        #[arg(long = "requests-per-minute")]
+        requests_per_minute: Option<u64>,
+
+        /// how much an API key can upload per minute, eg: "500 MiB"; 0 means no limit, the default.
+        #[arg(long = "bytes-per-minute")]
+        bytes_per_minute: Option<String>,
     },
 
     /// Convert an image.
@@ -58,11 +138,23 @@ pub enum Commands {
         /// Disable parallelized operations while converting the image to ASCII art.
         #[arg(long = "no-parallel")]
         no_parallel: bool,
+
+        /// What to write the ASCII art as: plain text (with ANSI colors), a self-contained HTML page or an SVG image.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// Only available with the "html" or "svg" format; the background color, in hex (eg: #1e1e1e). Default is none.
+        #[arg(long, value_parser = parse_color)]
+        background: Option<(u8, u8, u8)>,
+
+        /// Exports the character grid instead (its size, and every cell's character, color and density), for other tools to read.
+        #[arg(long, value_enum, conflicts_with_all = ["format", "background"])]
+        export: Option<ExportFormat>,
     },
 
     /// Turns a video into a sequence of frames, which can be played to the terminal or saved to a folder to then be played with the read command.
     Video {
-        /// The path to the video to convert to ascii.
+        /// The path to the video to convert to ascii. Animated GIFs, APNGs and WebPs work too, at their own pace, without needing ffmpeg.
         #[arg(short, long)]
         path: String,
 
@@ -86,7 +178,7 @@ pub enum Commands {
         #[arg(short, long, group = "frame-group")]
         savepath: Option<String>,
 
-        /// The delay between one frame and the other, in ms (milliseconds).
+        /// The delay between one frame and the other, in ms (milliseconds). If not provided, the frames play at the pace of the video.
         #[arg(long = "delay-frames", group = "frame-group")]
         delay_frames: Option<usize>,
 
@@ -101,11 +193,31 @@ pub enum Commands {
         /// Disable parallelized operations while converting the video to ASCII art.
         #[arg(long = "no-parallel")]
         no_parallel: bool,
+
+        /// Draws the video's audio under each frame, either as a small waveform or as a level meter.
+        #[arg(long = "audio-strip", value_enum)]
+        audio_strip: Option<AudioStrip>,
+
+        /// Only available when the "audio-strip" option is specified; the height (in characters) of the waveform strip. Default is 5.
+        #[arg(long = "audio-strip-height", requires = "audio_strip")]
+        audio_strip_height: Option<usize>,
+
+        /// What to write the frames as: text files (with ANSI colors), a single HTML page that plays them (saved at the savepath, or printed), or an SVG image per frame (needs a savepath).
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// Only available with the "html" or "svg" format; the background color, in hex (eg: #1e1e1e). Default is none.
+        #[arg(long, value_parser = parse_color)]
+        background: Option<(u8, u8, u8)>,
+
+        /// Exports the character grids of every frame instead (with their timestamps, and every cell's character, color and density) as a single file at the savepath, or printed.
+        #[arg(long, value_enum, conflicts_with_all = ["format", "background", "audio_strip", "delay_frames"])]
+        export: Option<ExportFormat>,
     },
 
     /// Creates an ASCII art of the waveform of the provided audio.
     Audio {
-         /// The path to the image to convert to ascii.
+         /// The path to the audio to convert to ascii. Video files work too, as long as they have an audio track. Use "-" to read from stdin.
          #[arg(short, long)]
          path: String,
  
@@ -113,6 +225,10 @@ pub enum Commands {
          #[arg(short = 'H', long)]
          height: Option<usize>,
  
+         /// The width (in characters) of the resulting ASCII art: each column shows the loudest samples of the stretch of audio it covers. If not provided, the waveform is as wide as the terminal (or 4096 columns when there's no terminal).
+         #[arg(short = 'W', long)]
+         width: Option<usize>,
+
          /// Whether to invert the image. (dark areas become lighter and vice-versa).
          #[arg(long)]
          invert: bool,
@@ -129,9 +245,170 @@ pub enum Commands {
          #[arg(long = "no-parallel")]
          no_parallel: bool,
 
-        //  /// specifies the audio type. (eg: mp3, ogg, etc...)
-        //  #[arg(long, short)]
-        //  media_type: String,
+         /// Where to start reading the audio from, either in seconds (eg: 12.5) or as a timestamp (eg: 01:30, 1:02:03.5). Default is the start of the track.
+         #[arg(long, value_parser = parse_timestamp)]
+         start: Option<f64>,
+
+         /// Where to stop reading the audio, either in seconds (eg: 12.5) or as a timestamp (eg: 01:30, 1:02:03.5). Default is the end of the track.
+         #[arg(long, value_parser = parse_timestamp)]
+         end: Option<f64>,
+
+         /// Specifies the audio type (eg: mp3, ogg, wav, etc...). If not provided, the file extension is used as a hint.
+         #[arg(long)]
+         format: Option<String>,
+
+         /// The index of the track to read. If not provided, the first track with an audio codec is used.
+         #[arg(long)]
+         track: Option<usize>,
+
+         /// Reads the input as raw PCM (samples with no container) in the given sample format; needs "rate" too.
+         #[arg(long, value_enum, requires = "rate", conflicts_with_all = ["format", "track"])]
+         raw: Option<RawFormat>,
+
+         /// Only available when the "raw" option is specified; the sample rate of the raw audio, in Hz.
+         #[arg(long, requires = "raw")]
+         rate: Option<u32>,
+
+         /// Only available when the "raw" option is specified; the number of interleaved channels of the raw audio. Default is 1.
+         #[arg(long, requires = "raw")]
+         channels: Option<usize>,
+
+         /// Adds a title line, an amplitude axis and a time ruler to the waveform.
+         #[arg(long)]
+         annotate: bool,
+
+         /// Only available when the "annotate" flag is specified; the unit of the amplitude axis.
+         #[arg(long = "amplitude-scale", value_enum, default_value_t = AmplitudeScale::Linear, requires = "annotate")]
+         amplitude_scale: AmplitudeScale,
+
+         /// Only available when the "annotate" flag is specified; draws horizontal gridlines at the given levels, in dBFS (eg: --gridlines=-6,-12); levels above 0 are left out.
+         #[arg(long, value_delimiter = ',', allow_negative_numbers = true, requires = "annotate")]
+         gridlines: Vec<f64>,
+
+         /// Exports the character grid of the waveform instead (with the sample rate and duration of the audio), for other tools to read.
+         #[arg(long, value_enum, conflicts_with = "annotate")]
+         export: Option<ExportFormat>,
+    },
+
+    /// Converts any image, video or audio file, finding out which it is from its contents (or its extension, if they can't tell).
+    Convert {
+        /// The path to the file to convert to ascii.
+        #[arg(short, long)]
+        path: String,
+
+        /// The width (in characters) of the resulting ASCII art; for audio, the number of columns of the waveform. If only the height is provided, this one will be inferred while trying to maintain the best proportions possible.
+        #[arg(short = 'W', long)]
+        width: Option<usize>,
+
+        /// The height (in characters) of the resulting ASCII art; for audio, the height of the highest waveform peak (255 if not provided).
+        #[arg(short = 'H', long)]
+        height: Option<usize>,
+
+        /// Only for videos; the number of total frames in the ASCII art video. The default is the original's video.
+        #[arg(short = 'f', long = "number-frames")]
+        n_frames: Option<usize>,
+
+        /// Whether to invert the ASCII art. (dark areas become lighter and vice-versa).
+        #[arg(long)]
+        invert: bool,
+
+        /// The savepath for the ASCII art; for videos, the folder to save the frames to. If not specified, the ASCII art is shown in-terminal.
+        #[arg(short, long)]
+        savepath: Option<String>,
+
+        /// Only for images and videos; whether the ASCII art should also contain colors, encoded in ANSI.
+        #[arg(short, long)]
+        colored: bool,
+
+        /// Makes it so every character is the most luminous one.
+        #[arg(short = 'u', long = "uniform-char")]
+        uniform_char: bool,
+
+        /// Disable parallelized operations while converting.
+        #[arg(long = "no-parallel")]
+        no_parallel: bool,
+
+        /// Only for audio; where to start reading it from, either in seconds (eg: 12.5) or as a timestamp (eg: 01:30).
+        #[arg(long, value_parser = parse_timestamp)]
+        start: Option<f64>,
+
+        /// Only for audio; where to stop reading it, either in seconds (eg: 12.5) or as a timestamp (eg: 01:30).
+        #[arg(long, value_parser = parse_timestamp)]
+        end: Option<f64>,
+
+        /// Exports the character grid instead, for other tools to read, as a single file at the savepath, or printed.
+        #[arg(long, value_enum)]
+        export: Option<ExportFormat>,
+    },
+
+    /// Converts many images at once, writing each one in the output folder at the same place it had in its input folder.
+    Batch {
+        /// The images to convert: files, folders (searched recursively for images) or glob patterns (eg: "photos/**/*.jpg", quoted so the shell doesn't expand it); can be repeated.
+        #[arg(short, long, required = true)]
+        input: Vec<String>,
+
+        /// The folder to write the ASCII art in; every file is named after its image, eg: shoe.png.txt.
+        #[arg(short, long)]
+        output: String,
+
+        /// The width (in characters) of the resulting ASCII art. If only the height is provided, this one will be inferred while trying to maintain the best proportions possible. If neither is, the image's height and width will be used.
+        #[arg(short = 'W', long)]
+        width: Option<usize>,
+
+        /// The height (in characters) of the resulting ASCII art. If only the width is provided, this one will be inferred while trying to maintain the best proportions possible. If neither is, the image's height and width will be used.
+        #[arg(short = 'H', long)]
+        height: Option<usize>,
+
+        /// Whether to invert the images. (dark areas become lighter and vice-versa).
+        #[arg(long)]
+        invert: bool,
+
+        /// Whether the ASCII art should also contain colors. Attention: colors are encoded in ANSI, be sure to use a text editor or terminal capable of displaying ANSI characters correctly.
+        #[arg(short, long)]
+        colored: bool,
+
+        /// Only available when the "colored" flag is specified; makes it so every character is the most luminous one.
+        #[arg(short = 'u', long = "uniform-char", requires = "colored")]
+        uniform_char: bool,
+
+        /// What to write the ASCII art as: plain text (with ANSI colors), a self-contained HTML page or an SVG image.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// Only available with the "html" or "svg" format; the background color, in hex (eg: #1e1e1e). Default is none.
+        #[arg(long, value_parser = parse_color)]
+        background: Option<(u8, u8, u8)>,
+
+        /// Exports the character grids instead (their size, and every cell's character, color and density), for other tools to read.
+        #[arg(long, value_enum, conflicts_with_all = ["format", "background"])]
+        export: Option<ExportFormat>,
+
+        /// Which images to leave alone because their ASCII art is up to date: none, the ones older than their output, or the ones whose contents and options haven't changed since the last batch (kept track of in the output folder).
+        #[arg(long, value_enum, default_value_t = SkipPolicy::Mtime)]
+        skip: SkipPolicy,
+
+        /// How many images to convert at the same time. Default is one per CPU.
+        #[arg(short, long)]
+        jobs: Option<usize>,
+    },
+
+    /// Prints what kind of media a file is and what's known about it (dimensions, frames, duration, sample rate...), along with a suggested size for its ASCII art, without converting it.
+    Probe {
+        /// The path to the file to look at.
+        #[arg(short, long)]
+        path: String,
+
+        /// The width (in characters) the ASCII art would be converted with. If neither this nor the height is provided, the suggested size fits the terminal.
+        #[arg(short = 'W', long)]
+        width: Option<usize>,
+
+        /// The height (in characters) the ASCII art would be converted with. If neither this nor the width is provided, the suggested size fits the terminal.
+        #[arg(short = 'H', long)]
+        height: Option<usize>,
+
+        /// Prints the information as JSON instead.
+        #[arg(long)]
+        json: bool,
     },
 
     /// Read a folder with frames created by the video command, and then show them in the terminal.
@@ -155,3 +432,10 @@ pub enum Commands {
         read_api_output_to_dir: Option<String>
     }
 }
+
+#[test]
+fn test_jobs_alias() {
+    let cli = Cli::try_parse_from(["anything_to_ascii", "api", "--jobs", "4"]).unwrap();
+
+    assert!(matches!(cli.command, Commands::Api { max_conversions: Some(4), .. }));
+}
@@ -0,0 +1,288 @@
+use std::{
+    fs::File,
+    io::{BufReader, Cursor, Read},
+    path::Path,
+};
+
+use image::ImageReader;
+
+use crate::{
+    audio::{audio_to_ascii::AudioOptions, source::AudioSource},
+    core::{
+        export::GridExport,
+        probe::{probe_audio, probe_image, probe_video, MediaKind},
+    },
+    image::decode::decode_image_bytes,
+    prelude::{AsciiAudio, AsciiImg, AsciiVid},
+    utils::utils::Verbosity,
+    video::{animation::is_animated, source::TempVideo, video_to_ascii::FrameProgress},
+    Error,
+};
+
+/// how many bytes from the start of a file [`sniff`] looks at.
+const HEAD_LEN: usize = 64;
+
+/// ## Info
+/// media converted to ASCII art, whichever kind it was; see [`convert_file`] and [`convert_bytes`].
+pub enum AsciiMedia {
+    Image(AsciiImg),
+    Video(AsciiVid),
+    Audio(AsciiAudio),
+}
+
+impl AsciiMedia {
+    pub fn kind(&self) -> MediaKind {
+        match self {
+            AsciiMedia::Image(_) => MediaKind::Image,
+            AsciiMedia::Video(_) => MediaKind::Video,
+            AsciiMedia::Audio(_) => MediaKind::Audio,
+        }
+    }
+}
+
+impl From<&AsciiMedia> for GridExport {
+    fn from(value: &AsciiMedia) -> Self {
+        match value {
+            AsciiMedia::Image(image) => GridExport::from(image),
+            AsciiMedia::Video(video) => GridExport::from(video),
+            AsciiMedia::Audio(audio) => GridExport::from(audio),
+        }
+    }
+}
+
+/// ## Info
+/// how to convert media of any kind; each option only counts for the kinds it makes sense for.
+///
+/// ## Args
+/// `height`: the height of the ASCII art in characters; for audio, the height of the highest peak (255 if `None`).
+/// `n_frames`: videos only; how many evenly spaced frames to convert.
+/// `colored`: images and videos only.
+/// `audio`: audio only; its `format` is ignored, since the one given to the conversion is used.
+#[derive(Debug, Clone, Default)]
+pub struct ConvertOptions {
+    pub height: Option<usize>,
+    pub width: Option<usize>,
+    pub n_frames: Option<usize>,
+    pub invert: bool,
+    pub colored: bool,
+    pub uniform: bool,
+    pub parallel: bool,
+    pub audio: AudioOptions,
+}
+
+/// ## Info
+/// tells the kind of media from the first bytes of a file, when they're distinctive enough. Containers that can hold
+/// either video or only audio (eg: Matroska) are left to the other checks.
+pub fn sniff(head: &[u8]) -> Option<MediaKind> {
+    if image::guess_format(head).is_ok() {
+        return Some(MediaKind::Image);
+    }
+
+    let at = |offset: usize, magic: &[u8]| head.get(offset..offset + magic.len()) == Some(magic);
+
+    if at(4, b"ftyp") {
+        // the "major brand" of MP4 files that only hold audio.
+        return match head.get(8..12) {
+            Some(b"M4A " | b"M4B " | b"M4P " | b"F4A ") => Some(MediaKind::Audio),
+            _ => Some(MediaKind::Video),
+        };
+    }
+
+    if at(0, b"OggS") {
+        // the first packet of the first stream names its codec.
+        return if at(28, b"\x80theora") {
+            Some(MediaKind::Video)
+        } else if at(28, b"\x01vorbis") || at(28, b"OpusHead") || at(28, b"\x7fFLAC") || at(28, b"Speex") {
+            Some(MediaKind::Audio)
+        } else {
+            None
+        };
+    }
+
+    match head {
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some(MediaKind::Audio),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'A', b'V', b'I', b' ', ..] => Some(MediaKind::Video),
+        [b'F', b'O', b'R', b'M', _, _, _, _, b'A', b'I', b'F', b'F' | b'C', ..] => Some(MediaKind::Audio),
+        [b'I', b'D', b'3', ..] | [b'f', b'L', b'a', b'C', ..] | [b'c', b'a', b'f', b'f', ..] => Some(MediaKind::Audio),
+        // the sync word of an MPEG audio (mp3) or ADTS (aac) frame.
+        [0xff, second, ..] if second & 0xe0 == 0xe0 => Some(MediaKind::Audio),
+        [b'F', b'L', b'V', ..] | [0x00, 0x00, 0x01, 0xba, ..] | [0x00, 0x00, 0x01, 0xb3, ..] => Some(MediaKind::Video),
+        _ => None,
+    }
+}
+
+/// ## Info
+/// the kind of media a file with the extension `extension` usually holds.
+pub fn kind_of_extension(extension: &str) -> Option<MediaKind> {
+    let extension = extension.to_lowercase();
+
+    match extension.as_str() {
+        "mp3" | "wav" | "ogg" | "oga" | "opus" | "flac" | "aiff" | "aif" | "m4a" | "aac" | "mka" | "wma" | "caf" => {
+            Some(MediaKind::Audio)
+        }
+        "mp4" | "m4v" | "mov" | "mkv" | "webm" | "avi" | "flv" | "wmv" | "mpg" | "mpeg" | "ts" | "m2ts" | "3gp" | "ogv" => {
+            Some(MediaKind::Video)
+        }
+        _ => image::ImageFormat::from_extension(&extension).map(|_| MediaKind::Image),
+    }
+}
+
+/// ## Info
+/// finds out what kind of media is at `path`: first from its first bytes, then from its extension, then by trying to
+/// read it as an image, as audio (with symphonia) and as a video (with ffmpeg), in that order. Animated GIFs, APNGs
+/// and WebPs count as videos.
+pub fn detect_file(path: &str) -> Result<MediaKind, Error> {
+    let mut head = Vec::with_capacity(HEAD_LEN);
+    File::open(path)?.take(HEAD_LEN as u64).read_to_end(&mut head)?;
+
+    let extension = Path::new(path).extension().map(|ext| ext.to_string_lossy().to_string());
+    if let Some(kind) = sniff(&head).or_else(|| extension.as_deref().and_then(kind_of_extension)) {
+        // animated images get converted like videos.
+        if kind == MediaKind::Image && is_animated(BufReader::new(File::open(path)?)) {
+            return Ok(MediaKind::Video);
+        }
+        return Ok(kind);
+    }
+
+    if probe_image(ImageReader::open(path)?)?.is_some() {
+        Ok(MediaKind::Image)
+    } else if probe_audio(Box::new(File::open(path)?), extension).is_ok() {
+        Ok(MediaKind::Audio)
+    } else if probe_video(path).is_ok() {
+        Ok(MediaKind::Video)
+    } else {
+        Err("couldn't tell whether the file is an image, a video or audio".into())
+    }
+}
+
+/// ## Info
+/// like [`detect_file`], for a file that's in memory.
+///
+/// ## Args
+/// `format`: the extension of the file, if known (eg: `webm`).
+pub fn detect_bytes(bytes: &[u8], format: Option<&str>) -> Result<MediaKind, Error> {
+    if let Some(kind) = sniff(&bytes[..bytes.len().min(HEAD_LEN)]).or_else(|| format.and_then(kind_of_extension)) {
+        if kind == MediaKind::Image && is_animated(Cursor::new(bytes)) {
+            return Ok(MediaKind::Video);
+        }
+        return Ok(kind);
+    }
+
+    if matches!(probe_image(ImageReader::new(Cursor::new(bytes))), Ok(Some(_))) {
+        Ok(MediaKind::Image)
+    } else if probe_audio(Box::new(Cursor::new(bytes.to_vec())), format.map(str::to_string)).is_ok() {
+        Ok(MediaKind::Audio)
+    } else if probe_video(TempVideo::from_bytes(bytes, format)?.path()).is_ok() {
+        Ok(MediaKind::Video)
+    } else {
+        Err("couldn't tell whether the file is an image, a video or audio".into())
+    }
+}
+
+/// ## Info
+/// converts the file at `path` to ASCII art, whatever kind of media it is; see [`detect_file`].
+pub fn convert_file(path: &str, options: &ConvertOptions, verbosity: Verbosity) -> Result<AsciiMedia, Error> {
+    let ConvertOptions { height, width, n_frames, invert, colored, uniform, parallel, .. } = options.clone();
+    let path = path.to_string();
+
+    Ok(match detect_file(&path)? {
+        MediaKind::Image => AsciiMedia::Image(if parallel {
+            AsciiImg::new_parallel_file(path, height, width, invert, !colored, uniform, verbosity)?
+        } else {
+            AsciiImg::new_sequential_file(path, height, width, invert, !colored, uniform, verbosity)?
+        }),
+        MediaKind::Video => AsciiMedia::Video(if parallel {
+            AsciiVid::new_paralleled(&path, n_frames, height, width, invert, !colored, uniform, verbosity)?
+        } else {
+            AsciiVid::new_sequential(&path, n_frames, height, width, invert, !colored, uniform, verbosity)?
+        }),
+        MediaKind::Audio => convert_audio(&path, None, options, verbosity)?,
+    })
+}
+
+/// ## Info
+/// converts a file that's in memory to ASCII art, whatever kind of media it is; see [`detect_bytes`].
+///
+/// ## Args
+/// `format`: the extension of the file, if known (eg: `webm`); used when the contents alone can't tell.
+pub fn convert_bytes(bytes: Vec<u8>, format: Option<&str>, options: &ConvertOptions, verbosity: Verbosity) -> Result<AsciiMedia, Error> {
+    let kind = detect_bytes(&bytes, format)?;
+
+    convert_detected(kind, bytes, format, options, verbosity)
+}
+
+/// ## Info
+/// converts a file that's in memory to ASCII art, as the kind of media it was already found to be.
+pub fn convert_detected(
+    kind: MediaKind,
+    bytes: Vec<u8>,
+    format: Option<&str>,
+    options: &ConvertOptions,
+    verbosity: Verbosity,
+) -> Result<AsciiMedia, Error> {
+    let ConvertOptions { height, width, n_frames, invert, colored, uniform, parallel, .. } = options.clone();
+
+    Ok(match kind {
+        MediaKind::Image => {
+            // the format only counts for images that can't be told apart by their first bytes.
+            let image = decode_image_bytes(&bytes, format)?;
+
+            AsciiMedia::Image(if parallel {
+                AsciiImg::new_parallel(image, height, width, invert, !colored, uniform, verbosity)?
+            } else {
+                AsciiImg::new_sequential(image, height, width, invert, !colored, uniform, verbosity)?
+            })
+        }
+        MediaKind::Video => AsciiMedia::Video(AsciiVid::from_reader(
+            bytes.as_slice(),
+            format,
+            n_frames,
+            height,
+            width,
+            invert,
+            !colored,
+            uniform,
+            parallel,
+            verbosity,
+            &FrameProgress::default(),
+        )?),
+        MediaKind::Audio => convert_audio(bytes, format, options, verbosity)?,
+    })
+}
+
+fn convert_audio(source: impl Into<AudioSource>, format: Option<&str>, options: &ConvertOptions, verbosity: Verbosity) -> Result<AsciiMedia, Error> {
+    let audio_options = AudioOptions { format: format.map(str::to_string), ..options.audio.clone() };
+    let height = options.height.unwrap_or(255);
+
+    Ok(AsciiMedia::Audio(if options.parallel {
+        AsciiAudio::new_parallel(source, height, options.width, options.uniform, options.invert, audio_options, verbosity)?
+    } else {
+        AsciiAudio::new_sequential(source, height, options.width, options.uniform, options.invert, audio_options, verbosity)?
+    }))
+}
+
+#[test]
+fn test_detection() {
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    assert_eq!(sniff(png), Some(MediaKind::Image));
+    assert_eq!(sniff(b"\0\0\0\x20ftypisom\0\0\x02\0"), Some(MediaKind::Video));
+    assert_eq!(sniff(b"\0\0\0\x20ftypM4A \0\0\x02\0"), Some(MediaKind::Audio));
+    assert_eq!(sniff(b"RIFF\x24\0\0\0WAVEfmt "), Some(MediaKind::Audio));
+    assert_eq!(sniff(b"RIFF\x24\0\0\0AVI LIST"), Some(MediaKind::Video));
+    assert_eq!(sniff(b"ID3\x04\0\0\0\0\0\0"), Some(MediaKind::Audio));
+    assert_eq!(sniff(&[0xff, 0xfb, 0x90, 0x64]), Some(MediaKind::Audio));
+    // Matroska can be either, so it's up to the extension.
+    assert_eq!(sniff(&[0x1a, 0x45, 0xdf, 0xa3]), None);
+
+    let mut ogg = b"OggS".to_vec();
+    ogg.resize(28, 0);
+    ogg.extend_from_slice(b"\x01vorbis");
+    assert_eq!(sniff(&ogg), Some(MediaKind::Audio));
+
+    assert_eq!(kind_of_extension("MKV"), Some(MediaKind::Video));
+    assert_eq!(kind_of_extension("mka"), Some(MediaKind::Audio));
+    assert_eq!(kind_of_extension("jpeg"), Some(MediaKind::Image));
+    assert_eq!(kind_of_extension("txt"), None);
+
+    assert_eq!(detect_bytes(&[0x1a, 0x45, 0xdf, 0xa3], Some("webm")).unwrap(), MediaKind::Video);
+}
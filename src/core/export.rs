@@ -0,0 +1,174 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::char::ColoredChar,
+    prelude::{AsciiAudio, AsciiImg, AsciiVid},
+    Error,
+};
+
+/// ## Info
+/// the machine-readable formats the character grid can be exported as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    /// MessagePack: the same fields as the JSON, in a compact binary form.
+    Msgpack,
+}
+
+/// ## Info
+/// the character grid of converted media, for tools that want more than the printed ASCII art: every cell's
+/// character, and for images and videos its color and density too.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GridExport {
+    /// `image`, `video` or `audio`.
+    pub kind: String,
+    /// the width of the grid (of each frame, for videos), in characters.
+    pub width: usize,
+    /// the height of the grid (of each frame, for videos), in characters.
+    pub height: usize,
+    pub frames: Vec<FrameExport>,
+    /// audio only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    /// audio only: the length of the rendered audio, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+}
+
+/// ## Info
+/// a single grid of characters; every list goes row by row.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameExport {
+    /// videos only: when the frame shows up, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<f64>,
+    /// every row of characters; hidden characters are spaces.
+    pub rows: Vec<String>,
+    /// the `[r, g, b]` color of every cell; not for audio.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rgb: Option<Vec<[u8; 3]>>,
+    /// the density (0-255) every character was picked for; not for audio.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub density: Option<Vec<u8>>,
+}
+
+impl FrameExport {
+    fn new(image: &AsciiImg, timestamp: Option<f64>) -> Self {
+        let cells = &image.0;
+
+        FrameExport {
+            timestamp,
+            rows: cells
+                .chunks()
+                .map(|row| row.iter().map(|cell| if cell.display { cell.ch } else { ColoredChar::CHAR_EMPTY }).collect())
+                .collect(),
+            rgb: Some(cells.iter().map(|cell| [cell.color.r, cell.color.g, cell.color.b]).collect()),
+            density: Some(cells.iter().map(|cell| cell.density).collect()),
+        }
+    }
+}
+
+impl GridExport {
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, Error> {
+        // with the field names, so it reads back like the JSON.
+        Ok(rmp_serde::to_vec_named(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(rmp_serde::from_slice(bytes)?)
+    }
+
+    pub fn to_bytes(&self, format: ExportFormat) -> Result<Vec<u8>, Error> {
+        match format {
+            ExportFormat::Json => self.to_json().map(String::into_bytes),
+            ExportFormat::Msgpack => self.to_msgpack(),
+        }
+    }
+}
+
+impl From<&AsciiImg> for GridExport {
+    fn from(value: &AsciiImg) -> Self {
+        GridExport {
+            kind: "image".to_string(),
+            width: value.0.columns,
+            height: value.0.rows,
+            frames: vec![FrameExport::new(value, None)],
+            sample_rate: None,
+            duration: None,
+        }
+    }
+}
+
+impl From<&AsciiVid> for GridExport {
+    fn from(value: &AsciiVid) -> Self {
+        let first = value.0.first();
+
+        GridExport {
+            kind: "video".to_string(),
+            width: first.map(|img| img.0.columns).unwrap_or(0),
+            height: first.map(|img| img.0.rows).unwrap_or(0),
+            frames: value
+                .0
+                .iter()
+                .zip(&value.1)
+                .map(|(frame, timestamp)| FrameExport::new(frame, Some(*timestamp)))
+                .collect(),
+            sample_rate: None,
+            duration: None,
+        }
+    }
+}
+
+impl From<&AsciiAudio> for GridExport {
+    fn from(value: &AsciiAudio) -> Self {
+        GridExport {
+            kind: "audio".to_string(),
+            width: value.0.columns,
+            height: value.0.rows,
+            frames: vec![FrameExport {
+                timestamp: None,
+                rows: value.0.chunks().map(|row| row.iter().collect()).collect(),
+                rgb: None,
+                density: None,
+            }],
+            sample_rate: Some(value.1.sample_rate),
+            duration: Some(value.1.duration),
+        }
+    }
+}
+
+#[test]
+fn test_grid_export() {
+    use crate::core::flat_matrix::FlatMatrix;
+
+    let cell = |density, color| ColoredChar::from_everything(density, color, true, false, false);
+    let image = AsciiImg(FlatMatrix::from(vec![vec![cell(250, (255, 0, 0)), cell(20, (0, 0, 255))]]));
+    let video = AsciiVid(vec![AsciiImg(image.0.clone()), image], vec![0.0, 0.04]);
+
+    let export = GridExport::from(&video);
+    assert_eq!((export.kind.as_str(), export.width, export.height), ("video", 2, 1));
+    assert_eq!(export.frames[1].timestamp, Some(0.04));
+    assert_eq!(export.frames[0].rows, vec!["@-".to_string()]);
+    assert_eq!(export.frames[0].rgb, Some(vec![[255, 0, 0], [0, 0, 255]]));
+    assert_eq!(export.frames[0].density, Some(vec![250, 20]));
+
+    assert_eq!(GridExport::from_json(&export.to_json().unwrap()).unwrap(), export);
+    let msgpack = export.to_msgpack().unwrap();
+    assert!(msgpack.len() < export.to_json().unwrap().len());
+    assert_eq!(GridExport::from_msgpack(&msgpack).unwrap(), export);
+
+    // the library types themselves round-trip too.
+    let json = serde_json::to_string(&video).unwrap();
+    let reloaded = serde_json::from_str::<AsciiVid>(&json).unwrap();
+    assert_eq!(reloaded.1, video.1);
+    assert_eq!(reloaded.0[0].0[(0, 1)].ch, '-');
+}
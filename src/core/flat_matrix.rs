@@ -1,13 +1,42 @@
 use std::ops::{Index, IndexMut};
 use rayon::{iter::{FromParallelIterator, IntoParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator}, slice::{ParallelSlice, ParallelSliceMut}};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RawFlatMatrix<T>")]
 pub struct FlatMatrix<T> {
     pub(crate) vec: Vec<T>,
     pub(crate) rows: usize,
     pub(crate) columns: usize,
 }
 
+/// ## Info
+/// a [`FlatMatrix`] as it comes out of the deserializer, before checking that its size adds up.
+#[derive(Deserialize)]
+struct RawFlatMatrix<T> {
+    vec: Vec<T>,
+    rows: usize,
+    columns: usize,
+}
+
+impl<T> TryFrom<RawFlatMatrix<T>> for FlatMatrix<T> {
+    type Error = String;
+
+    fn try_from(raw: RawFlatMatrix<T>) -> Result<Self, Self::Error> {
+        if raw.rows.checked_mul(raw.columns) != Some(raw.vec.len()) {
+            return Err(format!(
+                "a {}x{} matrix needs {} elements, not {}",
+                raw.rows,
+                raw.columns,
+                raw.rows.saturating_mul(raw.columns),
+                raw.vec.len()
+            ));
+        }
+
+        Ok(FlatMatrix { vec: raw.vec, rows: raw.rows, columns: raw.columns })
+    }
+}
+
 impl<T: Clone + Send + Sync> FlatMatrix<T> {
     
     
@@ -205,4 +234,14 @@ impl<T> From<Vec<Vec<T>>> for FlatMatrix<T> {
     fn from(value: Vec<Vec<T>>) -> Self {
         value.into_iter().collect()
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_deserialize_checks_size() {
+    let matrix = serde_json::from_str::<FlatMatrix<u8>>(r#"{"vec":[1,2,3,4,5,6],"rows":2,"columns":3}"#).unwrap();
+    assert_eq!(matrix[(1, 2)], 6);
+
+    assert!(serde_json::from_str::<FlatMatrix<u8>>(r#"{"vec":[1,2,3,4,5],"rows":2,"columns":3}"#).is_err());
+    let overflowing = format!(r#"{{"vec":[],"rows":{},"columns":2}}"#, usize::MAX);
+    assert!(serde_json::from_str::<FlatMatrix<u8>>(&overflowing).is_err());
+}
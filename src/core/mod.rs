@@ -1,4 +1,8 @@
 pub mod char;
 pub mod algo;
 pub mod flat_matrix;
-pub mod cli;
\ No newline at end of file
+pub mod cli;
+pub mod render;
+pub mod export;
+pub mod probe;
+pub mod convert;
\ No newline at end of file
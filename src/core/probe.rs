@@ -0,0 +1,337 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufRead, Seek},
+    path::Path,
+};
+
+use image::{metadata::Orientation, ImageDecoder, ImageReader};
+use serde::{Deserialize, Serialize};
+use symphonia::{
+    core::{
+        formats::FormatOptions,
+        io::{MediaSource, MediaSourceStream, MediaSourceStreamOptions},
+        meta::MetadataOptions,
+        probe::Hint,
+    },
+    default::{get_codecs, get_probe},
+};
+
+use crate::{
+    audio::audio_to_ascii::select_track,
+    core::convert::detect_file,
+    image::decode::check_format,
+    utils::utils::DemureUnwrap,
+    video::animation::{read_animation_file, Animation},
+    Error,
+};
+
+/// ## Info
+/// what kind of media a file holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Image,
+    Video,
+    Audio,
+}
+
+impl Display for MediaKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            MediaKind::Image => "image",
+            MediaKind::Video => "video",
+            MediaKind::Audio => "audio",
+        };
+
+        write!(f, "{}", kind)
+    }
+}
+
+/// ## Info
+/// the size, in characters, of some ASCII art.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CharSize {
+    pub width: usize,
+    pub height: usize,
+}
+
+/// ## Info
+/// what [`probe`] found out about a file, without converting it; what a kind of media doesn't have (or what couldn't be
+/// read) is left empty.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProbeReport {
+    pub kind: MediaKind,
+    /// the container or file format, eg: `png`, `mov,mp4,m4a,3gp,3g2,mj2`, `wav`.
+    pub container: Option<String>,
+    /// the codec of the video or audio track.
+    pub codec: Option<String>,
+    /// in pixels; images and videos only.
+    pub width: Option<u32>,
+    /// in pixels; images and videos only.
+    pub height: Option<u32>,
+    /// videos only.
+    pub frames: Option<u64>,
+    /// videos only.
+    pub fps: Option<f64>,
+    /// in seconds; videos and audio only.
+    pub duration: Option<f64>,
+    /// in Hz; audio only.
+    pub sample_rate: Option<u32>,
+    /// audio only.
+    pub channels: Option<usize>,
+    /// a size for the ASCII art of an image or video; see [`ProbeReport::suggest`].
+    pub suggested_output: Option<CharSize>,
+}
+
+impl ProbeReport {
+    fn new(kind: MediaKind) -> Self {
+        ProbeReport {
+            kind,
+            container: None,
+            codec: None,
+            width: None,
+            height: None,
+            frames: None,
+            fps: None,
+            duration: None,
+            sample_rate: None,
+            channels: None,
+            suggested_output: None,
+        }
+    }
+
+    /// ## Info
+    /// fills in a suggested size for the ASCII art of the file. With a `width` or a `height` (in characters), it's the
+    /// size the image and video commands would make with the same options. Without either, the art is fit to
+    /// `terminal` (its columns and rows, if known): as wide as the terminal, unless that would make it taller than it.
+    /// This differs from the commands, which keep the size of the source when given neither.
+    pub fn suggest(&mut self, width: Option<usize>, height: Option<usize>, terminal: Option<(usize, usize)>) {
+        let (Some(src_width), Some(src_height)) = (self.width, self.height) else {
+            return;
+        };
+        let (src_width, src_height) = (src_width as usize, src_height as usize);
+        if src_width == 0 || src_height == 0 {
+            return;
+        }
+
+        let (width, height) = match (width, height, terminal) {
+            (None, None, Some((columns, rows))) => {
+                let fit = (Some(columns), None).demure_unwrap(src_width, src_height);
+                if fit.1 > rows {
+                    (None, Some(rows)).demure_unwrap(src_width, src_height)
+                } else {
+                    fit
+                }
+            }
+            (width, height, _) => (width, height).demure_unwrap(src_width, src_height),
+        };
+
+        self.suggested_output = Some(CharSize { width, height });
+    }
+}
+
+impl Display for ProbeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "kind: {}", self.kind)?;
+        if let Some(container) = &self.container {
+            writeln!(f, "container: {}", container)?;
+        }
+        if let Some(codec) = &self.codec {
+            writeln!(f, "codec: {}", codec)?;
+        }
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            writeln!(f, "dimensions: {}x{} pixels", width, height)?;
+        }
+        if let Some(frames) = self.frames {
+            writeln!(f, "frames: {}", frames)?;
+        }
+        if let Some(fps) = self.fps {
+            writeln!(f, "fps: {:.3}", fps)?;
+        }
+        if let Some(duration) = self.duration {
+            writeln!(f, "duration: {:.3}s", duration)?;
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            writeln!(f, "sample rate: {} Hz", sample_rate)?;
+        }
+        if let Some(channels) = self.channels {
+            writeln!(f, "channels: {}", channels)?;
+        }
+        if let Some(output) = self.suggested_output {
+            writeln!(f, "suggested output: {}x{} characters", output.width, output.height)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// ## Info
+/// finds out what kind of media is at `path` (see [`detect_file`]) and reads what it can about it, without decoding it.
+pub fn probe(path: &str) -> Result<ProbeReport, Error> {
+    match detect_file(path)? {
+        MediaKind::Image => probe_image(ImageReader::open(path)?)?.ok_or_else(|| "the image is in a format that isn't supported".into()),
+        MediaKind::Video => match read_animation_file(path)? {
+            Some(animation) => Ok(probe_animation(&animation)),
+            None => probe_video(path),
+        },
+        MediaKind::Audio => {
+            let extension = Path::new(path).extension().map(|ext| ext.to_string_lossy().to_lowercase());
+            probe_audio(Box::new(File::open(path)?), extension)
+        }
+    }
+}
+
+/// ## Info
+/// the format and size of an image, or `None` if its contents aren't of an image format. The size is the one after
+/// turning the image the way its EXIF orientation says, like [`decode_image`](crate::image::decode::decode_image) does.
+pub(crate) fn probe_image<R: BufRead + Seek>(reader: ImageReader<R>) -> Result<Option<ProbeReport>, Error> {
+    let reader = reader.with_guessed_format()?;
+    let Some(format) = reader.format() else {
+        return Ok(None);
+    };
+    check_format(format)?;
+
+    let mut decoder = reader.into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    let (width, height) = match decoder.orientation().unwrap_or(Orientation::NoTransforms) {
+        Orientation::Rotate90 | Orientation::Rotate270 | Orientation::Rotate90FlipH | Orientation::Rotate270FlipH => (height, width),
+        _ => (width, height),
+    };
+    let mut report = ProbeReport::new(MediaKind::Image);
+    report.container = format.extensions_str().first().map(|ext| ext.to_string());
+    report.width = Some(width);
+    report.height = Some(height);
+
+    Ok(Some(report))
+}
+
+fn probe_animation(animation: &Animation) -> ProbeReport {
+    let mut report = ProbeReport::new(MediaKind::Video);
+    report.container = animation.format.extensions_str().first().map(|ext| ext.to_string());
+    if let Some((_, first)) = animation.frames.first() {
+        report.width = Some(first.width());
+        report.height = Some(first.height());
+    }
+    report.frames = Some(animation.frames.len() as u64);
+    report.duration = Some(animation.duration).filter(|duration| *duration > 0.0);
+    report.fps = report.duration.map(|duration| animation.frames.len() as f64 / duration);
+
+    report
+}
+
+pub(crate) fn probe_video(path: &str) -> Result<ProbeReport, Error> {
+    video_rs::init().map_err(|_| Error::InternalError("ffmpeg couldn't start"))?;
+    let decoder = video_rs::Decoder::new(Path::new(path))?;
+
+    let mut report = ProbeReport::new(MediaKind::Video);
+    let (width, height) = decoder.size();
+    report.width = Some(width);
+    report.height = Some(height);
+    report.frames = decoder.frames().ok().filter(|frames| *frames > 0);
+    report.fps = Some(decoder.frame_rate() as f64).filter(|fps| *fps > 0.0);
+    report.duration = decoder.duration().ok().map(|time| time.as_secs_f64()).filter(|duration| *duration > 0.0);
+
+    let (_, reader, stream_index) = decoder.into_parts();
+    report.container = Some(reader.input.format().name().to_string());
+    report.codec = reader.input.stream(stream_index).map(|stream| stream.parameters().id().name().to_string());
+    if report.duration.is_none() {
+        // the stream doesn't know, but the container might; it counts in microseconds.
+        report.duration = Some(reader.input.duration() as f64 / 1_000_000.0).filter(|duration| *duration > 0.0);
+    }
+
+    Ok(report)
+}
+
+/// ## Info
+/// what symphonia can read about the first audio track of `media`; `extension` is a hint of its format.
+pub(crate) fn probe_audio(media: Box<dyn MediaSource>, extension: Option<String>) -> Result<ProbeReport, Error> {
+    let media_src_stream = MediaSourceStream::new(media, MediaSourceStreamOptions::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = &extension {
+        hint.with_extension(extension);
+    }
+
+    let probed = get_probe().format(&hint, media_src_stream, &FormatOptions::default(), &MetadataOptions::default())?;
+    let track = select_track(probed.format.tracks(), None)?;
+    let params = &track.codec_params;
+
+    let mut report = ProbeReport::new(MediaKind::Audio);
+    // symphonia doesn't name the containers it reads, so the extension has to do.
+    report.container = extension;
+    report.codec = get_codecs().get_codec(params.codec).map(|codec| codec.short_name.to_string());
+    report.sample_rate = params.sample_rate;
+    report.channels = params.channels.map(|channels| channels.count());
+    report.duration = match (params.n_frames, params.time_base) {
+        (Some(n_frames), Some(time_base)) => {
+            let time = time_base.calc_time(n_frames);
+            Some(time.seconds as f64 + time.frac)
+        }
+        (Some(n_frames), None) => params.sample_rate.map(|rate| n_frames as f64 / rate as f64),
+        _ => None,
+    };
+
+    Ok(report)
+}
+
+/// ## Info
+/// the columns and rows of the terminal the program runs in, if it has one; otherwise the `COLUMNS` and `LINES`
+/// environment variables, if they're set.
+pub fn terminal_dimensions() -> Option<(usize, usize)> {
+    if let Some((terminal_size::Width(columns), terminal_size::Height(rows))) = terminal_size::terminal_size() {
+        return Some((columns as usize, rows as usize));
+    }
+
+    let variable = |name| std::env::var(name).ok().and_then(|value| value.parse::<usize>().ok());
+    variable("COLUMNS").zip(variable("LINES"))
+}
+
+#[test]
+fn test_probe_suggest() {
+    let mut report = ProbeReport::new(MediaKind::Video);
+    report.width = Some(1920);
+    report.height = Some(1080);
+
+    // as wide as the terminal, as long as it fits.
+    report.suggest(None, None, Some((120, 40)));
+    assert_eq!(report.suggested_output, Some(CharSize { width: 120, height: 34 }));
+
+    // too tall: as tall as the terminal instead.
+    report.suggest(None, None, Some((200, 40)));
+    assert_eq!(report.suggested_output, Some(CharSize { width: 143, height: 40 }));
+
+    // the options win over the terminal, and without either the source's size is used.
+    report.suggest(Some(80), None, Some((200, 40)));
+    assert_eq!(report.suggested_output, Some(CharSize { width: 80, height: 23 }));
+    report.suggest(None, None, None);
+    assert_eq!(report.suggested_output, Some(CharSize { width: 1920, height: 540 }));
+
+    assert!(report.to_string().contains("dimensions: 1920x1080 pixels\n"));
+    assert!(serde_json::to_string(&report).unwrap().contains("\"kind\":\"video\""));
+
+    let mut audio = ProbeReport::new(MediaKind::Audio);
+    audio.suggest(None, None, Some((120, 40)));
+    assert_eq!(audio.suggested_output, None);
+}
+
+#[test]
+fn test_probe_image_orientation() {
+    use image::{codecs::jpeg::JpegEncoder, RgbImage};
+    use std::io::Cursor;
+
+    // a 3x1 JPEG, with an EXIF block saying it has to be turned 90° clockwise.
+    let mut jpeg = vec![];
+    JpegEncoder::new(&mut jpeg).encode_image(&RgbImage::new(3, 1)).unwrap();
+    let tiff: &[u8] = b"MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0\x06\0\0\0\0\0\0";
+    let mut app1 = vec![0xff, 0xe1, 0x00, 0x22];
+    app1.extend_from_slice(b"Exif\0\0");
+    app1.extend_from_slice(tiff);
+    jpeg.splice(2..2, app1);
+
+    let mut report = probe_image(ImageReader::new(Cursor::new(jpeg))).unwrap().unwrap();
+    assert_eq!((report.width, report.height), (Some(1), Some(3)));
+
+    report.suggest(None, None, None);
+    assert_eq!(report.suggested_output, Some(CharSize { width: 1, height: 1 }));
+}
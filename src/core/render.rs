@@ -0,0 +1,308 @@
+use std::fmt::Write;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{
+        char::{grayscale_calc, ColoredChar},
+        flat_matrix::FlatMatrix,
+    },
+    image::image_to_ascii::AsciiImg,
+    read::ansi::parse_ansi,
+    video::video_to_ascii::AsciiVid,
+};
+
+/// the page [`Grid::video_html`] fills in: `{{style}}`, `{{frames}}` and `{{timestamps}}` get replaced.
+const VIDEO_PLAYER: &str = include_str!("render/player.html");
+
+/// ## Info
+/// what the ASCII art gets written as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// plain text, with ANSI escape codes for the colors.
+    #[default]
+    Text,
+    /// a self-contained HTML page; videos get a player with every frame embedded.
+    Html,
+    /// an SVG image; videos get one per frame.
+    Svg,
+}
+
+/// ## Info
+/// how the HTML and SVG renderers draw the ASCII art.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    /// the color behind the characters; none leaves it transparent (white for most viewers). Uncolored characters get
+    /// black or white, whichever stands out more from it.
+    pub background: Option<(u8, u8, u8)>,
+    /// the size of the font, in pixels; a character is 0.6 of it wide and all of it tall.
+    pub font_size: f64,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions { background: None, font_size: 12.0 }
+    }
+}
+
+impl RenderOptions {
+    fn foreground(&self) -> &'static str {
+        match self.background {
+            Some((r, g, b)) if grayscale_calc(r, g, b) < 128 => "#fff",
+            _ => "#000",
+        }
+    }
+
+    fn style(&self) -> String {
+        let background = self.background.map_or("transparent".to_string(), css_color);
+
+        format!(
+            "body{{margin:0;background:{}}}pre.ascii{{margin:0;padding:{}px;color:{};font:{}px/1 monospace}}",
+            background,
+            self.font_size,
+            self.foreground(),
+            self.font_size,
+        )
+    }
+}
+
+/// ## Info
+/// a run of characters next to each other in a row that share a color; `None` is the default text color.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Run {
+    color: Option<(u8, u8, u8)>,
+    text: String,
+}
+
+/// ## Info
+/// ASCII art split into rows of same-colored runs of characters, ready to be drawn as HTML or SVG. Made from an
+/// [`AsciiImg`] (or any `FlatMatrix<ColoredChar>`), or from ASCII art that's already been turned into ANSI text.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Grid {
+    rows: Vec<Vec<Run>>,
+    columns: usize,
+}
+
+impl Grid {
+    /// ## Info
+    /// reads ASCII art printed with ANSI colors, eg: the `text` of an API response; see [`parse_ansi`].
+    pub fn from_ansi(text: &str) -> Self {
+        Grid::from(&parse_ansi(text))
+    }
+
+    /// ## Info
+    /// adds the rows of `other` under the ones of this grid.
+    pub fn append(&mut self, other: Grid) {
+        self.columns = self.columns.max(other.columns);
+        self.rows.extend(other.rows);
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// ## Info
+    /// roughly how much memory the grid takes, in bytes.
+    pub(crate) fn bytes(&self) -> usize {
+        self.rows.iter().flatten().map(|run| run.text.len() + 8).sum()
+    }
+
+    /// ## Info
+    /// the ASCII art as a `<pre>` element with a `<span>` for every colored run, to put in a page that has the
+    /// `ascii` class styled.
+    pub fn html_fragment(&self) -> String {
+        let mut html = String::from("<pre class=\"ascii\">");
+
+        for (index, row) in self.rows.iter().enumerate() {
+            if index > 0 {
+                html.push('\n');
+            }
+
+            for run in row {
+                match run.color {
+                    Some(color) => {
+                        let _ = write!(html, "<span style=\"color:{}\">{}</span>", css_color(color), escape(&run.text));
+                    }
+                    None => html.push_str(&escape(&run.text)),
+                }
+            }
+        }
+
+        html.push_str("</pre>");
+        html
+    }
+
+    /// ## Info
+    /// the ASCII art as a self-contained HTML page.
+    pub fn html(&self, options: &RenderOptions) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>ASCII art</title>\n<style>{}</style>\n</head>\n<body>\n{}\n</body>\n</html>\n",
+            options.style(),
+            self.html_fragment(),
+        )
+    }
+
+    /// ## Info
+    /// the ASCII art as an SVG image: a `<text>` per row, with a `<tspan>` for every colored run.
+    pub fn svg(&self, options: &RenderOptions) -> String {
+        let size = options.font_size;
+        let (width, height) = (self.columns as f64 * size * 0.6, self.rows.len() as f64 * size);
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"monospace\" font-size=\"{2}\" fill=\"{3}\" xml:space=\"preserve\" style=\"white-space:pre\">\n",
+            width,
+            height,
+            size,
+            options.foreground(),
+        );
+
+        if let Some(background) = options.background {
+            let _ = writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>", css_color(background));
+        }
+
+        for (index, row) in self.rows.iter().enumerate() {
+            // the baseline sits a bit above the bottom of the line, leaving room for the descenders.
+            let _ = write!(svg, "<text x=\"0\" y=\"{}\">", (index as f64 + 0.8) * size);
+
+            for run in row {
+                match run.color {
+                    Some(color) => {
+                        let _ = write!(svg, "<tspan fill=\"{}\">{}</tspan>", css_color(color), escape(&run.text));
+                    }
+                    None => svg.push_str(&escape(&run.text)),
+                }
+            }
+
+            svg.push_str("</text>\n");
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// ## Info
+    /// the frames of a video as a self-contained HTML page that plays them at the pace of their timestamps (in
+    /// seconds), with a button to pause and a slider to seek.
+    pub fn video_html(frames: &[Grid], timestamps: &[f64], options: &RenderOptions) -> String {
+        let frames = frames.iter().map(|frame| frame.html_fragment()).collect::<Vec<_>>();
+
+        // nothing in the embedded JSON can close the `<script>` it's in.
+        let frames = serde_json::to_string(&frames).unwrap_or_default().replace("</", "<\\/");
+        let timestamps = serde_json::to_string(timestamps).unwrap_or_default();
+
+        VIDEO_PLAYER
+            .replace("{{style}}", &options.style())
+            .replace("{{timestamps}}", &timestamps)
+            .replace("{{frames}}", &frames)
+    }
+
+    fn push_row(&mut self, row: RowBuilder) {
+        self.columns = self.columns.max(row.columns);
+        self.rows.push(row.runs);
+    }
+}
+
+impl From<&FlatMatrix<ColoredChar>> for Grid {
+    fn from(value: &FlatMatrix<ColoredChar>) -> Self {
+        let mut grid = Grid::default();
+
+        for cells in value.chunks() {
+            let mut row = RowBuilder::default();
+
+            for cell in cells {
+                // hidden characters still take up their cell, so the rows stay aligned.
+                match (cell.display, cell.is_grayscale()) {
+                    (false, _) => row.push(ColoredChar::CHAR_EMPTY, None),
+                    (true, true) => row.push(cell.ch, None),
+                    (true, false) => row.push(cell.ch, Some((cell.color.r, cell.color.g, cell.color.b))),
+                }
+            }
+
+            grid.push_row(row);
+        }
+
+        grid
+    }
+}
+
+#[derive(Default)]
+struct RowBuilder {
+    runs: Vec<Run>,
+    columns: usize,
+}
+
+impl RowBuilder {
+    fn push(&mut self, ch: char, color: Option<(u8, u8, u8)>) {
+        self.columns += 1;
+
+        match self.runs.last_mut() {
+            // the color of a space doesn't show, so it never starts a run of its own.
+            Some(run) if run.color == color || ch == ' ' => run.text.push(ch),
+            _ => self.runs.push(Run { color, text: ch.to_string() }),
+        }
+    }
+}
+
+fn css_color((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+impl AsciiImg {
+    /// ## Info
+    /// the ASCII art as a self-contained HTML page, with its colors.
+    pub fn to_html(&self, options: &RenderOptions) -> String {
+        Grid::from(&self.0).html(options)
+    }
+
+    /// ## Info
+    /// the ASCII art as an SVG image, with its colors.
+    pub fn to_svg(&self, options: &RenderOptions) -> String {
+        Grid::from(&self.0).svg(options)
+    }
+}
+
+impl AsciiVid {
+    /// ## Info
+    /// the frames as a self-contained HTML page that plays them; see [`Grid::video_html`].
+    pub fn to_html(&self, options: &RenderOptions) -> String {
+        let frames = self.0.iter().map(|frame| Grid::from(&frame.0)).collect::<Vec<_>>();
+
+        Grid::video_html(&frames, &self.1, options)
+    }
+}
+
+#[test]
+fn test_render() {
+    let red = ColoredChar::from_everything(200, (255, 0, 0), true, false, false);
+    let gray = ColoredChar::from_everything(200, (80, 80, 80), true, false, false);
+    let matrix = FlatMatrix::from(vec![vec![red.clone(), red.clone(), gray.clone()], vec![gray.clone(), red, gray]]);
+
+    let grid = Grid::from(&matrix);
+    assert_eq!((grid.rows(), grid.columns()), (2, 3));
+    assert_eq!(grid.html_fragment(), "<pre class=\"ascii\"><span style=\"color:#ff0000\">KK</span>K\nK<span style=\"color:#ff0000\">K</span>K</pre>");
+
+    // the ANSI text of the same art makes the same grid.
+    colored::control::set_override(true);
+    let ansi = matrix.into_iter_vecs().map(|row| row.into_iter().map(|c| c.to_string()).collect::<String>()).collect::<Vec<_>>().join("\n");
+    assert_eq!(Grid::from_ansi(&ansi), grid);
+    assert_eq!(Grid::from_ansi("\x1b[38;2;1;2;3ma<\x1b[0mb").html_fragment(), "<pre class=\"ascii\"><span style=\"color:#010203\">a&lt;</span>b</pre>");
+
+    let svg = grid.svg(&RenderOptions { background: Some((0, 0, 0)), font_size: 10.0 });
+    assert!(svg.contains("width=\"18\" height=\"20\""));
+    assert!(svg.contains("fill=\"#fff\""));
+    assert!(svg.contains("<tspan fill=\"#ff0000\">KK</tspan>K</text>"));
+
+    let video = Grid::video_html(&[grid.clone(), grid], &[0.0, 0.5], &RenderOptions::default());
+    assert!(video.contains("[0.0,0.5]"));
+    assert!(!video.contains("{{"));
+    assert!(!video.contains("</pre>"));
+}
@@ -0,0 +1,114 @@
+use std::io::{BufRead, Cursor, Seek};
+
+use image::{metadata::Orientation, DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+
+use crate::Error;
+
+/// ## Info
+/// decodes an image, telling its format from its contents (or from the one already set on `reader`, if they can't
+/// tell), and turns it the way its EXIF orientation says, so that phone photos don't come out sideways.
+///
+/// ## Note
+/// fails with [`Error::UnsupportedImage`] if the format isn't recognized, or if this build can't read it.
+pub fn decode_image<R: BufRead + Seek>(reader: ImageReader<R>) -> Result<DynamicImage, Error> {
+    let reader = reader.with_guessed_format()?;
+    let Some(format) = reader.format() else {
+        return Err(Error::UnsupportedImage("the format couldn't be recognized".to_string()));
+    };
+    check_format(format)?;
+
+    let mut decoder = reader.into_decoder()?;
+    // a broken EXIF block shouldn't keep the image from being converted.
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    Ok(image)
+}
+
+/// ## Info
+/// like [`decode_image`], for the file at `path`; its extension is used when the contents can't tell the format.
+pub fn decode_image_file(path: &str) -> Result<DynamicImage, Error> {
+    decode_image(ImageReader::open(path)?)
+}
+
+/// ## Info
+/// like [`decode_image`], for an image that's in memory.
+///
+/// ## Args
+/// `format`: the extension of the image, if known (eg: `tga`); only used when the contents can't tell the format.
+pub fn decode_image_bytes(bytes: &[u8], format: Option<&str>) -> Result<DynamicImage, Error> {
+    let mut reader = ImageReader::new(Cursor::new(bytes));
+    if let Some(format) = format.and_then(ImageFormat::from_extension) {
+        reader.set_format(format);
+    }
+
+    decode_image(reader)
+}
+
+/// ## Info
+/// fails with [`Error::UnsupportedImage`], naming the format and the cargo feature that would read it, if this build
+/// can't read images in `format`.
+pub fn check_format(format: ImageFormat) -> Result<(), Error> {
+    let (feature, enabled) = match format {
+        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif => return Ok(()),
+        ImageFormat::WebP => ("webp", cfg!(feature = "webp")),
+        ImageFormat::Bmp => ("bmp", cfg!(feature = "bmp")),
+        ImageFormat::Tiff => ("tiff", cfg!(feature = "tiff")),
+        ImageFormat::Tga => ("tga", cfg!(feature = "tga")),
+        ImageFormat::Ico => ("ico", cfg!(feature = "ico")),
+        ImageFormat::Qoi => ("qoi", cfg!(feature = "qoi")),
+        ImageFormat::Pnm => ("pnm", cfg!(feature = "pnm")),
+        ImageFormat::OpenExr => ("exr", cfg!(feature = "exr")),
+        ImageFormat::Hdr => ("hdr", cfg!(feature = "hdr")),
+        ImageFormat::Dds => ("dds", cfg!(feature = "dds")),
+        ImageFormat::Farbfeld => ("ff", cfg!(feature = "ff")),
+        ImageFormat::Avif => ("avif", cfg!(feature = "avif")),
+        format => return Err(Error::UnsupportedImage(format!("{} images can't be converted", format_name(format)))),
+    };
+
+    if enabled {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedImage(format!(
+            "the image is a {}, but this build was made without the `{}` feature",
+            format_name(format),
+            feature
+        )))
+    }
+}
+
+/// ## Info
+/// the name of `format`, as its usual extension in uppercase, eg: `WEBP`.
+pub fn format_name(format: ImageFormat) -> String {
+    match format.extensions_str().first() {
+        Some(extension) => extension.to_uppercase(),
+        None => format!("{:?}", format),
+    }
+}
+
+#[test]
+fn test_decode_image() {
+    use image::{codecs::jpeg::JpegEncoder, RgbImage};
+
+    // a 3x1 JPEG, with an EXIF block saying it has to be turned 90° clockwise.
+    let mut jpeg = vec![];
+    JpegEncoder::new(&mut jpeg).encode_image(&RgbImage::new(3, 1)).unwrap();
+    let tiff: &[u8] = b"MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0\x06\0\0\0\0\0\0";
+    let mut app1 = vec![0xff, 0xe1, 0x00, 0x22];
+    app1.extend_from_slice(b"Exif\0\0");
+    app1.extend_from_slice(tiff);
+    jpeg.splice(2..2, app1);
+
+    let image = decode_image_bytes(&jpeg, None).unwrap();
+    assert_eq!((image.width(), image.height()), (1, 3));
+
+    // PCX is recognized, but never read.
+    let error = decode_image_bytes(b"\x0a\x05\x01\x08", None).unwrap_err();
+    assert!(matches!(&error, Error::UnsupportedImage(detail) if detail.contains("PCX")));
+
+    // farbfeld is read with the default features: a 1x1 white pixel.
+    let farbfeld = [b"farbfeld".as_slice(), &[0, 0, 0, 1, 0, 0, 0, 1], &[0xff; 8]].concat();
+    assert_eq!(decode_image_bytes(&farbfeld, None).unwrap().width(), 1);
+    assert!(matches!(decode_image_bytes(b"not an image", None), Err(Error::UnsupportedImage(_))));
+}
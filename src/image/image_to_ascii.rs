@@ -1,13 +1,16 @@
 use std::fmt::Display;
-use image::{DynamicImage, GenericImageView, ImageReader, Rgba};
+use image::{DynamicImage, GenericImageView, Rgba};
 use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
 use crate::core::algo::algo_sequential;
 use crate::core::char::Concat;
+use crate::image::decode::decode_image_file;
 use crate::report;
 use crate::utils::utils::Verbosity;
 use crate::{core::{algo::algo_parallel, char::ColoredChar, flat_matrix::FlatMatrix}, utils::utils::DemureUnwrap, Error};
 use crate::timestamp;
 
+#[derive(Serialize, Deserialize)]
 pub struct AsciiImg(pub FlatMatrix<ColoredChar>);
 
 impl AsciiImg {
@@ -112,9 +115,9 @@ impl AsciiImg {
     ) -> Result<Self, Error> {
         report!(verbosity, @verbose "opening image");
         let image = if grayscale {
-            ImageReader::open(path)?.decode()?.grayscale()
+            decode_image_file(&path)?.grayscale()
         } else {
-            ImageReader::open(path)?.decode()?
+            decode_image_file(&path)?
         };
         report!(verbosity, @verbose "finished opening image");
 
@@ -132,9 +135,9 @@ impl AsciiImg {
     ) -> Result<Self, Error> {
         report!(verbosity, @verbose "opening image");
         let image = if grayscale {
-            ImageReader::open(path)?.decode()?.grayscale()
+            decode_image_file(&path)?.grayscale()
         } else {
-            ImageReader::open(path)?.decode()?
+            decode_image_file(&path)?
         };
         report!(verbosity, @verbose "finished opening image");
 
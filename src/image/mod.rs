@@ -1 +1,2 @@
-pub mod image_to_ascii;
\ No newline at end of file
+pub mod image_to_ascii;
+pub mod decode;
\ No newline at end of file
@@ -5,6 +5,7 @@ pub mod video;
 pub mod audio;
 pub mod read;
 pub mod api;
+pub mod batch;
 
 use std::time::{SystemTime, UNIX_EPOCH};
 
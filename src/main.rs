@@ -1,15 +1,27 @@
 use anything_to_ascii::api::api::*;
+use anything_to_ascii::api::jobs::*;
+use anything_to_ascii::api::metrics::{healthz, metrics, readyz, Health, Metrics, RequestMetrics};
+use anything_to_ascii::api::auth::{api_catcher, ApiKeys};
+use anything_to_ascii::api::cache::{api_cache_stats, ResultCache};
+use anything_to_ascii::api::docs::docs_routes;
+use anything_to_ascii::api::playground::playground_routes;
+use anything_to_ascii::api::config::{api_preflight, Conversions, Cors, ServerSettings};
+use anything_to_ascii::audio::{audio_to_ascii::AudioOptions, source::{AudioSource, RawPcm}};
+use anything_to_ascii::batch::batch::{run_batch, BatchOptions};
+use anything_to_ascii::core::convert::{convert_file, AsciiMedia, ConvertOptions};
+use anything_to_ascii::core::probe::{probe, terminal_dimensions};
 use anything_to_ascii::report;
 use anything_to_ascii::utils::utils::Verbosity;
 use anything_to_ascii::{
-    core::cli::*,
+    core::{cli::*, export::{ExportFormat, GridExport}, render::{Grid, OutputFormat, RenderOptions}},
     prelude::{AsciiAudio, AsciiImg, AsciiVid},
     read::read::*,
 }; //read::read_video::{read_dir_no_parallel, read_dir_parallel}};
 use clap::Parser;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use rocket::{routes, tokio, Config};
-use std::{error::Error, ffi::OsStr, fs, io::Write, path::Path, thread, time};
+use rocket::{catchers, figment::Figment, routes, tokio};
+use serde::Serialize;
+use std::{error::Error, ffi::OsStr, fs, io::Write, path::{Path, PathBuf}, sync::Arc, thread, time};
 use anything_to_ascii::timestamp;
 
 #[tokio::main]
@@ -24,8 +36,49 @@ async fn main() -> Result<(), Box<dyn Error>> {
         (true, true) => panic!("...please message the creator, because something *very* weird just happened"),
     };
     match cli.command {
-        Commands::Api { no_parallel, port } => {
-            let _ = build_rocket(no_parallel, port).launch().await;
+        Commands::Api {
+            config,
+            address,
+            port,
+            workers,
+            no_parallel,
+            max_threads,
+            max_conversions,
+            timeout,
+            cors_origins,
+            tls_cert,
+            tls_key,
+            image_limit,
+            video_limit,
+            audio_limit,
+            cache_size,
+            cache_dir,
+            api_keys_file,
+            requests_per_minute,
+            bytes_per_minute,
+        } => {
+            // the flags that were given win over the config file and the environment.
+            let figment = ServerSettings::figment(config.as_deref());
+            let figment = merge_flag(figment, "address", address);
+            let figment = merge_flag(figment, "port", port);
+            let figment = merge_flag(figment, "workers", workers);
+            let figment = merge_flag(figment, "parallel", no_parallel.then_some(false));
+            let figment = merge_flag(figment, "max_threads", max_threads);
+            let figment = merge_flag(figment, "max_conversions", max_conversions);
+            let figment = merge_flag(figment, "conversion_timeout", timeout);
+            let figment = merge_flag(figment, "cors_origins", cors_origins);
+            let figment = merge_flag(figment, "tls.certs", tls_cert);
+            let figment = merge_flag(figment, "tls.key", tls_key);
+            let figment = merge_flag(figment, "limits.image", image_limit);
+            let figment = merge_flag(figment, "limits.video", video_limit);
+            let figment = merge_flag(figment, "limits.audio", audio_limit);
+            let figment = merge_flag(figment, "cache_size", cache_size);
+            let figment = merge_flag(figment, "cache_dir", cache_dir);
+            let figment = merge_flag(figment, "api_keys_file", api_keys_file);
+            let figment = merge_flag(figment, "rate_limit.requests_per_minute", requests_per_minute);
+            let figment = merge_flag(figment, "rate_limit.bytes_per_minute", bytes_per_minute);
+
+            let _ = build_rocket(figment)?.launch().await;
         }
 
         Commands::Image {
@@ -37,6 +90,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
             colored,
             uniform_char,
             no_parallel,
+            format,
+            background,
+            export,
         } => {
             let name = path;
 
@@ -46,9 +102,20 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 AsciiImg::new_sequential_file(name, height, width, invert, !colored, uniform_char, verbosity)?
             };
 
+            if let Some(export) = export {
+                return write_export(GridExport::from(&x), export, savepath);
+            }
+
+            let options = RenderOptions { background, ..RenderOptions::default() };
+            let contents = match format {
+                OutputFormat::Text => x.to_string(),
+                OutputFormat::Html => x.to_html(&options),
+                OutputFormat::Svg => x.to_svg(&options),
+            };
+
             match savepath {
-                Some(path) => fs::write(path, x.to_string())?,
-                None => println!("{}", x),
+                Some(path) => fs::write(path, contents)?,
+                None => println!("{}", contents),
             }
         }
 
@@ -63,6 +130,11 @@ async fn main() -> Result<(), Box<dyn Error>> {
             no_parallel,
             delay_frames,
             n_frames,
+            audio_strip,
+            audio_strip_height,
+            format,
+            background,
+            export,
         } => {
             let video = if !no_parallel {
                 AsciiVid::new_paralleled(
@@ -88,70 +160,128 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 )?
             };
 
-            match savepath {
-                Some(sv_path) => {
-                    let name = Path::new(&sv_path)
-                        .file_stem()
-                        .unwrap_or(OsStr::new("video"))
-                        .to_string_lossy()
-                        .to_string();
-                    let save_path = Path::new(&sv_path);
+            if let Some(export) = export {
+                return write_export(GridExport::from(&video), export, savepath);
+            }
 
-                    let len = video.0.len();
+            let strips = match audio_strip {
+                Some(kind) => Some(video.audio_strips(&path, kind, audio_strip_height.unwrap_or(5), verbosity)?),
+                None => None,
+            };
+
+            if format != OutputFormat::Text {
+                let options = RenderOptions { background, ..RenderOptions::default() };
+                let grids = video
+                    .0
+                    .iter()
+                    .enumerate()
+                    .map(|(index, img)| {
+                        let mut grid = Grid::from(&img.0);
+                        if let Some(strip) = strips.as_ref().and_then(|strips| strips.get(index)) {
+                            grid.append(Grid::from_ansi(strip));
+                        }
+                        grid
+                    })
+                    .collect::<Vec<_>>();
 
-                    fs::create_dir_all(save_path).expect("failed to write folders");
+                match (format, savepath) {
+                    (OutputFormat::Svg, Some(sv_path)) => {
+                        let frames = grids.iter().map(|grid| grid.svg(&options)).collect::<Vec<_>>();
+                        save_frames(&sv_path, &frames, "svg");
+                    }
+                    (OutputFormat::Svg, None) => return Err("the svg format needs a --savepath to write the frames to".into()),
+                    (_, Some(sv_path)) => fs::write(sv_path, Grid::video_html(&grids, &video.1, &options))?,
+                    (_, None) => println!("{}", Grid::video_html(&grids, &video.1, &options)),
+                }
 
-                    (0..len).into_par_iter().for_each(|index| {
-                        let frame_file_name = format!("{}_frame{}.txt", name, index);
+                return Ok(());
+            }
 
-                        // Combine save folder path with the frame file name
-                        let frame_file_path = save_path.join(frame_file_name);
+            let mut frames = video.0.iter().map(|img| img.to_string()).collect::<Vec<_>>();
 
-                        let text = &video.0[index];
+            if let Some(strips) = strips {
+                frames.iter_mut().zip(strips).for_each(|(frame, strip)| {
+                    frame.push('\n');
+                    frame.push_str(&strip);
+                });
+            }
 
-                        fs::write(frame_file_path, text.to_string()).expect("failed to write");
-                    });
-                }
+            match savepath {
+                Some(sv_path) => save_frames(&sv_path, &frames, "txt"),
 
-                None => match delay_frames {
-                    Some(delay) => play_ascii_frames(
-                        video.0.into_iter().map(|img| img.to_string()).collect(),
-                        delay as usize,
-                    ),
-                    None => play_ascii_frames(
-                        video.0.into_iter().map(|img| img.to_string()).collect(),
-                        100,
-                    ),
-                },
+                None => play_ascii_frames(
+                    frames,
+                    Some(video.1.as_slice()),
+                    delay_frames,
+                ),
             }
         }
         Commands::Audio {
             path,
             height,
+            width,
             invert,
             savepath,
             uniform_char,
             no_parallel,
+            start,
+            end,
+            format,
+            track,
+            raw,
+            rate,
+            channels,
+            annotate,
+            amplitude_scale,
+            gridlines,
+            export,
         } => {
+            let raw = raw.zip(rate).map(|(format, sample_rate)| RawPcm {
+                format,
+                sample_rate,
+                channels: channels.unwrap_or(1),
+            });
+            let options = AudioOptions { start, end, format, track, raw };
+
+            let width = width.or_else(|| terminal_dimensions().map(|(columns, _)| columns));
+
+            let source = if path == "-" {
+                AudioSource::stdin()
+            } else {
+                AudioSource::from(&path)
+            };
+
             let waveform = if !no_parallel {
                 AsciiAudio::new_parallel(
-                    &path,
+                    source,
                     height.unwrap_or(255),
+                    width,
                     uniform_char,
                     invert,
+                    options,
                     verbosity,
                 )?
             } else {
                 AsciiAudio::new_sequential(
-                    &path,
+                    source,
                     height.unwrap_or(255),
+                    width,
                     uniform_char,
                     invert,
+                    options,
                     verbosity
                 )?
             };
 
-            let contents = waveform.to_string();
+            if let Some(export) = export {
+                return write_export(GridExport::from(&waveform), export, savepath);
+            }
+
+            let contents = if annotate {
+                waveform.annotated(amplitude_scale, &gridlines)
+            } else {
+                waveform.to_string()
+            };
                 
             match savepath {
                 Some(savepath) => fs::write(savepath, contents)?,
@@ -159,6 +289,106 @@ async fn main() -> Result<(), Box<dyn Error>> {
             }
         }
 
+        Commands::Convert {
+            path,
+            width,
+            height,
+            n_frames,
+            invert,
+            savepath,
+            colored,
+            uniform_char,
+            no_parallel,
+            start,
+            end,
+            export,
+        } => {
+            let options = ConvertOptions {
+                height,
+                width,
+                n_frames,
+                invert,
+                colored,
+                uniform: uniform_char,
+                parallel: !no_parallel,
+                audio: AudioOptions { start, end, ..AudioOptions::default() },
+            };
+
+            let media = convert_file(&path, &options, verbosity)?;
+            report!(verbosity, @normal "converted the file as {}", (media.kind()));
+
+            if let Some(export) = export {
+                return write_export(GridExport::from(&media), export, savepath);
+            }
+
+            match (media, savepath) {
+                (AsciiMedia::Video(video), Some(sv_path)) => {
+                    let frames = video.0.iter().map(|img| img.to_string()).collect::<Vec<_>>();
+                    save_frames(&sv_path, &frames, "txt");
+                }
+                (AsciiMedia::Video(video), None) => {
+                    play_ascii_frames(video.0.iter().map(|img| img.to_string()).collect(), Some(video.1.as_slice()), None);
+                }
+                (AsciiMedia::Image(image), savepath) => write_text(image.to_string(), savepath)?,
+                (AsciiMedia::Audio(audio), savepath) => write_text(audio.to_string(), savepath)?,
+            }
+        }
+
+        Commands::Batch {
+            input,
+            output,
+            width,
+            height,
+            invert,
+            colored,
+            uniform_char,
+            format,
+            background,
+            export,
+            skip,
+            jobs,
+        } => {
+            let options = BatchOptions {
+                height,
+                width,
+                invert,
+                colored,
+                uniform: uniform_char,
+                format,
+                background,
+                export,
+                skip,
+                jobs: jobs.unwrap_or(0),
+            };
+
+            let report = run_batch(&input, Path::new(&output), &options, verbosity)?;
+
+            println!(
+                "{} converted, {} skipped, {} failed",
+                report.converted.len(),
+                report.skipped.len(),
+                report.failed.len()
+            );
+            for (path, error) in &report.failed {
+                println!("  {}: {}", path.display(), error);
+            }
+
+            if !report.failed.is_empty() {
+                return Err(format!("{} images couldn't be converted", report.failed.len()).into());
+            }
+        }
+
+        Commands::Probe { path, width, height, json } => {
+            let mut report = probe(&path)?;
+            report.suggest(width, height, terminal_dimensions());
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report);
+            }
+        }
+
         Commands::Read {
             path,
             no_parallel,
@@ -184,25 +414,83 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     }
 
                     return Ok(());
-                } else {if !no_parallel {
-                    read_dir_parallel(path)
                 } else {
-                    read_dir_no_parallel(path)
-                }}
+                    let frames = if !no_parallel {
+                        read_dir_parallel(path)
+                    } else {
+                        read_dir_no_parallel(path)
+                    };
+
+                    frames.map(|frames| frames.iter().map(|frame| frame.to_string()).collect())
+                }
             }?;
 
-            play_ascii_frames(frames, frame_delay.unwrap_or(100));
+            play_ascii_frames(frames, None, Some(frame_delay.unwrap_or(100)));
         }
     }
 
     Ok(())
 }
 
-fn play_ascii_frames(frames: Vec<String>, frame_delay: usize) {
+/// ## Info
+/// writes an exported character grid to `savepath`, or to stdout.
+fn write_export(export: GridExport, format: ExportFormat, savepath: Option<String>) -> Result<(), Box<dyn Error>> {
+    let bytes = export.to_bytes(format)?;
+
+    match savepath {
+        Some(path) => fs::write(path, bytes)?,
+        None => std::io::stdout().write_all(&bytes)?,
+    }
+
+    Ok(())
+}
+
+/// ## Info
+/// writes ASCII art to `savepath`, or to stdout.
+fn write_text(contents: String, savepath: Option<String>) -> Result<(), Box<dyn Error>> {
+    match savepath {
+        Some(path) => fs::write(path, contents)?,
+        None => println!("{}", contents),
+    }
+
+    Ok(())
+}
+
+/// ## Info
+/// writes every frame to its own file in the `sv_path` folder, named after the folder: `<name>_frame<index>.<extension>`.
+fn save_frames(sv_path: &str, frames: &[String], extension: &str) {
+    let name = Path::new(&sv_path)
+        .file_stem()
+        .unwrap_or(OsStr::new("video"))
+        .to_string_lossy()
+        .to_string();
+    let save_path = Path::new(&sv_path);
+
+    let len = frames.len();
+
+    fs::create_dir_all(save_path).expect("failed to write folders");
+
+    (0..len).into_par_iter().for_each(|index| {
+        let frame_file_name = format!("{}_frame{}.{}", name, index, extension);
+
+        // Combine save folder path with the frame file name
+        let frame_file_path = save_path.join(frame_file_name);
+
+        let text = &frames[index];
+
+        fs::write(frame_file_path, text).expect("failed to write");
+    });
+}
+
+/// shows `frames` one after the other, `frame_delay` milliseconds apart if given; otherwise at the pace of their
+/// `timestamps` (in seconds), or 100 ms apart without them.
+fn play_ascii_frames(frames: Vec<String>, timestamps: Option<&[f64]>, frame_delay: Option<usize>) {
     // Clear the terminal
     print!("\x1B[2J");
 
-    for frame in frames {
+    let start = time::Instant::now();
+    let count = frames.len();
+    for (index, frame) in frames.into_iter().enumerate() {
         // Move cursor to the top-left corner
         print!("\x1B[H");
 
@@ -212,20 +500,64 @@ fn play_ascii_frames(frames: Vec<String>, frame_delay: usize) {
         // Flush stdout to ensure the frame is displayed immediately
         std::io::stdout().flush().unwrap();
 
-        // Wait for the specified delay
-        thread::sleep(time::Duration::from_millis(frame_delay as u64));
+        // there's nothing to wait for after the last frame
+        if index + 1 == count {
+            break;
+        }
+
+        // Wait for the specified delay, or until the next frame is due
+        let next = timestamps.and_then(|timestamps| Some((timestamps.get(index + 1)? - timestamps.first()?).max(0.0)));
+        match (frame_delay, next) {
+            (Some(frame_delay), _) => thread::sleep(time::Duration::from_millis(frame_delay as u64)),
+            (None, Some(next)) => thread::sleep(time::Duration::from_secs_f64(next).saturating_sub(start.elapsed())),
+            (None, None) => thread::sleep(time::Duration::from_millis(100)),
+        }
     }
 }
 
-fn build_rocket(no_parallel: bool, port: Option<u16>) -> rocket::Rocket<rocket::Build> {
-    let config = Config {
-        port: port.unwrap_or(8000),
-        ..Default::default()
-    };
-    
-    if !no_parallel {
-        rocket::custom(config).mount("/", routes![api_img_to_ascii_parallel, api_video_to_ascii_parallel, api_audio_to_ascii_parallel])
-    } else {
-        rocket::custom(config).mount("/", routes![api_img_to_ascii_sequential, api_video_to_ascii_sequential, api_audio_to_ascii_sequential])
+fn merge_flag<T: Serialize>(figment: Figment, key: &str, value: Option<T>) -> Figment {
+    match value {
+        Some(value) => figment.merge((key, value)),
+        None => figment,
     }
 }
+
+fn build_rocket(figment: Figment) -> Result<rocket::Rocket<rocket::Build>, Box<dyn Error>> {
+    let settings = figment.extract::<ServerSettings>()?;
+
+    let conversions = Arc::new(Conversions::new(&settings));
+    let cache = ResultCache::new(settings.cache_size * 1024 * 1024, settings.cache_dir.clone().map(PathBuf::from));
+    let keys = ApiKeys::new(&settings)?;
+    let metrics_registry = Arc::new(Metrics::new());
+
+    let rocket = rocket::custom(figment)
+        .attach(Cors { origins: settings.cors_origins.clone() })
+        .attach(Health)
+        .attach(RequestMetrics(metrics_registry.clone()))
+        .manage(JobQueue::new())
+        .manage(conversions)
+        .manage(cache)
+        .manage(keys)
+        .manage(metrics_registry)
+        .register("/api", catchers![api_catcher])
+        .mount("/", routes![
+            api_preflight,
+            api_img_to_ascii,
+            api_video_to_ascii,
+            api_video_to_ascii_stream,
+            api_audio_to_ascii,
+            api_convert,
+            api_create_job,
+            api_job_status,
+            api_job_result,
+            api_delete_job,
+            api_cache_stats,
+            healthz,
+            readyz,
+            metrics,
+        ])
+        .mount("/", docs_routes())
+        .mount("/", playground_routes());
+
+    Ok(rocket)
+}
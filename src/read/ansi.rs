@@ -0,0 +1,190 @@
+use std::{fs, path::Path};
+
+use colored::CustomColor;
+
+use crate::{
+    core::{char::ColoredChar, flat_matrix::FlatMatrix},
+    image::image_to_ascii::AsciiImg,
+    Error,
+};
+
+/// the 16 basic ANSI colors, as xterm shows them.
+const BASIC_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// ## Info
+/// reads ASCII art printed with ANSI colors (eg: the frames saved by the video command) back into a grid of
+/// [`ColoredChar`]s. The foreground colors can be 24-bit (`38;2;r;g;b`), from the 256 color palette (`38;5;n`) or one of
+/// the 16 basic ones (`30`-`37`, `90`-`97`); every other escape code is dropped.
+///
+/// ## Note
+/// lines shorter than the longest one get padded with spaces. Characters without a color get the gray of their
+/// density, like a grayscale conversion would give them. The density of a character is guessed from which one it
+/// is, assuming the art wasn't inverted; characters this crate never picks count as the densest.
+pub fn parse_ansi(text: &str) -> FlatMatrix<ColoredChar> {
+    let mut rows = text.lines().map(parse_line).collect::<Vec<_>>();
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+
+    for row in rows.iter_mut() {
+        row.resize_with(columns, || cell(ColoredChar::CHAR_EMPTY, None));
+    }
+
+    FlatMatrix::from(rows)
+}
+
+fn parse_line(line: &str) -> Vec<ColoredChar> {
+    let mut cells = vec![];
+    let mut color = None;
+    let mut chars = line.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\x1b' {
+            if !ch.is_control() {
+                cells.push(cell(ch, color));
+            }
+            continue;
+        }
+
+        // only CSI sequences (`ESC [ <parameters> <final byte>`) are understood; a lone ESC is dropped.
+        if chars.peek() != Some(&'[') {
+            continue;
+        }
+        chars.next();
+
+        let mut parameters = String::new();
+        let mut last = None;
+        for ch in chars.by_ref() {
+            if ('\x40'..='\x7e').contains(&ch) {
+                last = Some(ch);
+                break;
+            }
+            parameters.push(ch);
+        }
+
+        if last == Some('m') {
+            color = sgr_color(&parameters, color);
+        }
+    }
+
+    cells
+}
+
+fn cell(ch: char, color: Option<(u8, u8, u8)>) -> ColoredChar {
+    let density = ColoredChar::density_of(ch).unwrap_or(if ch.is_whitespace() { 0 } else { u8::MAX });
+    let (r, g, b) = color.unwrap_or((density, density, density));
+
+    ColoredChar {
+        color: CustomColor::new(r, g, b),
+        ch,
+        density,
+        display: true,
+    }
+}
+
+/// ## Info
+/// the foreground color after the SGR escape with parameters `parameters` (eg: `38;2;255;0;0`), starting from `color`;
+/// `None` is the default color.
+fn sgr_color(parameters: &str, mut color: Option<(u8, u8, u8)>) -> Option<(u8, u8, u8)> {
+    let codes = parameters.split(';').map(|code| code.parse::<u16>().unwrap_or(0)).collect::<Vec<_>>();
+    let channel = |index: usize| codes.get(index).map(|code| (*code).min(255) as u8);
+    let mut index = 0;
+
+    while index < codes.len() {
+        match codes[index] {
+            0 | 39 => color = None,
+            code @ 30..=37 => color = Some(BASIC_COLORS[(code - 30) as usize]),
+            code @ 90..=97 => color = Some(BASIC_COLORS[(code - 90 + 8) as usize]),
+            // the background gets skipped the same way, so its values aren't read as codes.
+            code @ (38 | 48) => match codes.get(index + 1) {
+                Some(2) => {
+                    if let (38, Some(r), Some(g), Some(b)) = (code, channel(index + 2), channel(index + 3), channel(index + 4)) {
+                        color = Some((r, g, b));
+                    }
+                    index += 4;
+                }
+                Some(5) => {
+                    if let (38, Some(n)) = (code, channel(index + 2)) {
+                        color = Some(palette_color(n));
+                    }
+                    index += 2;
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+        index += 1;
+    }
+
+    color
+}
+
+/// ## Info
+/// a color of the 256 color palette: the 16 basic colors, a 6x6x6 color cube, then 24 shades of gray.
+fn palette_color(n: u8) -> (u8, u8, u8) {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+    match n {
+        0..16 => BASIC_COLORS[n as usize],
+        16..232 => {
+            let n = n - 16;
+            (LEVELS[(n / 36) as usize], LEVELS[(n / 6 % 6) as usize], LEVELS[(n % 6) as usize])
+        }
+        _ => {
+            let gray = 8 + (n - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+impl AsciiImg {
+    /// ## Info
+    /// reads ASCII art printed with ANSI colors back; see [`parse_ansi`].
+    pub fn from_ansi(text: &str) -> Self {
+        Self(parse_ansi(text))
+    }
+
+    /// ## Info
+    /// reads a file of ASCII art printed with ANSI colors, eg: a frame saved by the video command.
+    pub fn from_ansi_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self::from_ansi(&fs::read_to_string(path)?))
+    }
+}
+
+#[test]
+fn test_parse_ansi() {
+    colored::control::set_override(true);
+
+    // what the conversions print comes back the same.
+    let red = ColoredChar::from_everything(200, (255, 0, 0), true, false, false);
+    let gray = ColoredChar::from_everything(250, (250, 250, 250), true, false, false);
+    let image = AsciiImg(FlatMatrix::from(vec![vec![red.clone(), gray.clone()], vec![gray, red]]));
+    let parsed = AsciiImg::from_ansi(&image.to_string());
+    assert_eq!(parsed.to_string(), image.to_string());
+    assert_eq!(parsed.0[(0, 0)].density, 195);
+
+    // 256 and 16 colors, resets, background colors, multi-byte characters and ragged lines.
+    let parsed = parse_ansi("\x1b[38;5;196mé\x1b[0mx\x1b[48;2;1;2;3m\x1b[92m→\n\x1b[38;5;244ma");
+    assert_eq!((parsed.rows, parsed.columns), (2, 3));
+    assert_eq!(parsed[(0, 0)].ch, 'é');
+    assert_eq!(parsed[(0, 0)].color, CustomColor::new(255, 0, 0));
+    assert_eq!(parsed[(0, 1)].color, CustomColor::new(255, 255, 255));
+    assert_eq!(parsed[(0, 2)].color, CustomColor::new(0, 255, 0));
+    assert_eq!(parsed[(1, 0)].color, CustomColor::new(128, 128, 128));
+    assert_eq!(parsed[(1, 2)].ch, ' ');
+}
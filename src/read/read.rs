@@ -1,8 +1,11 @@
 use std::{fs, rc::Rc, sync::Arc};
-use crate::Error;
+use crate::{image::image_to_ascii::AsciiImg, Error};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-pub fn read_dir_no_parallel(read_path: String) -> Result<Vec<String>, Error> {
+/// ## Info
+/// reads the frames saved by the video command in `read_path` (the `<name>_frame<index>` files), in order, parsing
+/// their ANSI colors back; see [`crate::read::ansi::parse_ansi`].
+pub fn read_dir_no_parallel(read_path: String) -> Result<Vec<AsciiImg>, Error> {
     let read_path = read_path.chars().rev().skip_while(|c| *c == '/').collect::<String>().chars().rev().collect::<String>();
 
     let dir = fs::read_dir(&read_path)?;
@@ -26,14 +29,17 @@ pub fn read_dir_no_parallel(read_path: String) -> Result<Vec<String>, Error> {
     let read_path = Rc::new(read_path);
 
     vec_name_frames.frame_sort();
-    let vec_strings = vec_name_frames
+    let vec_frames = vec_name_frames
         .into_iter()
-        .map(|path| fs::read_to_string(format!("{}/{}", Rc::clone(&read_path), path)).unwrap()).collect::<Vec<String>>();
+        .map(|path| AsciiImg::from_ansi_file(format!("{}/{}", Rc::clone(&read_path), path)))
+        .collect::<Result<Vec<AsciiImg>, Error>>()?;
 
-    Ok(vec_strings)
+    Ok(vec_frames)
 }
 
-pub fn read_dir_parallel(read_path: String) -> Result<Vec<String>, Error> {
+/// ## Info
+/// the parallel version of [`read_dir_no_parallel`].
+pub fn read_dir_parallel(read_path: String) -> Result<Vec<AsciiImg>, Error> {
     let read_path = read_path.chars().rev().skip_while(|c| *c == '/').collect::<String>().chars().rev().collect::<String>();
     let dir = fs::read_dir(&read_path)?;
 
@@ -60,11 +66,12 @@ pub fn read_dir_parallel(read_path: String) -> Result<Vec<String>, Error> {
     let read_path = Arc::new(read_path);
 
     vec_name_frames.frame_sort();
-    let vec_strings = vec_name_frames
+    let vec_frames = vec_name_frames
         .into_par_iter()
-        .map(|path| fs::read_to_string(format!("{}/{}", Arc::clone(&read_path), path)).unwrap()).collect::<Vec<String>>();
+        .map(|path| AsciiImg::from_ansi_file(format!("{}/{}", Arc::clone(&read_path), path)))
+        .collect::<Result<Vec<AsciiImg>, Error>>()?;
 
-    Ok(vec_strings)
+    Ok(vec_frames)
 }
 
 pub fn read_video_from_api_file(path: &String) -> Result<Vec<String>, Error> {
@@ -5,6 +5,9 @@ pub enum Error {
     #[error("image error: {0}")]
     ImageError(#[from] image::error::ImageError),
 
+    #[error("unsupported image: {0}")]
+    UnsupportedImage(String),
+
     #[error("video error: {0}")]
     VideoError(#[from] video_rs::Error),
 
@@ -17,8 +20,23 @@ pub enum Error {
     #[error("io error: {0}")]
     IO(#[from] std::io::Error),
 
+    #[error("json error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("msgpack encoding error: {0}")]
+    MsgPackEncodeError(#[from] rmp_serde::encode::Error),
+
+    #[error("msgpack decoding error: {0}")]
+    MsgPackDecodeError(#[from] rmp_serde::decode::Error),
+
+    #[error("invalid glob pattern: {0}")]
+    GlobError(#[from] glob::PatternError),
+
     #[error("error: {0}")]
-    LibError(&'static str)
+    LibError(&'static str),
+
+    #[error("internal error: {0}")]
+    InternalError(&'static str)
 }
 
 impl From<&'static str> for Error {
@@ -34,4 +34,86 @@ pub enum Verbosity {
     Silent,
     Normal,
     Verbose,
-}
\ No newline at end of file
+}
+
+/// ## Info
+/// parses a point in time, given either as plain seconds (`90`, `12.5`) or as a `mm:ss` / `hh:mm:ss` timestamp (`01:30`, `1:02:03.5`),
+/// into seconds. Meant to be used as a `clap` value parser.
+pub fn parse_timestamp(value: &str) -> Result<f64, String> {
+    let invalid = || format!("\"{}\" is not a valid time: use seconds (eg: 90, 12.5) or a timestamp (eg: 01:30, 1:02:03.5)", value);
+
+    let parts = value.trim().split(':').collect::<Vec<_>>();
+
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+
+    let mut seconds = 0.0;
+    for (index, part) in parts.iter().enumerate() {
+        let is_last = index == parts.len() - 1;
+
+        // a sign would let a segment count backwards, eg: "1:-30"
+        if part.starts_with(['-', '+']) {
+            return Err(invalid());
+        }
+
+        let part = if is_last {
+            part.parse::<f64>().map_err(|_| invalid())?
+        } else {
+            part.parse::<u64>().map_err(|_| invalid())? as f64
+        };
+
+        // every unit but the biggest one has to stay under 60
+        if index != 0 && part >= 60.0 {
+            return Err(invalid());
+        }
+
+        seconds = seconds * 60.0 + part;
+    }
+
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(invalid());
+    }
+
+    Ok(seconds)
+}
+
+#[test]
+fn test_parse_timestamp() {
+    assert_eq!(parse_timestamp("90"), Ok(90.0));
+    assert_eq!(parse_timestamp("12.5"), Ok(12.5));
+    assert_eq!(parse_timestamp("01:30"), Ok(90.0));
+    assert_eq!(parse_timestamp("1:02:03.5"), Ok(3723.5));
+    assert!(parse_timestamp("1:75").is_err());
+    assert!(parse_timestamp("-3").is_err());
+    assert!(parse_timestamp("1:-30").is_err());
+    assert!(parse_timestamp("1:-0.5").is_err());
+    assert!(parse_timestamp("abc").is_err());
+}
+
+/// ## Info
+/// parses a color given as hex (`#1e1e1e`, `1e1e1e` or the short `#111`) into its red, green and blue channels.
+/// Meant to be used as a `clap` value parser.
+pub fn parse_color(value: &str) -> Result<(u8, u8, u8), String> {
+    let invalid = || format!("\"{}\" is not a valid color: use hex, eg: #1e1e1e or #111", value);
+
+    let hex = value.trim().trim_start_matches('#');
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect::<String>(),
+        6 => hex.to_string(),
+        _ => return Err(invalid()),
+    };
+
+    let channel = |index: usize| hex.get(index..index + 2).and_then(|part| u8::from_str_radix(part, 16).ok()).ok_or_else(invalid);
+
+    Ok((channel(0)?, channel(2)?, channel(4)?))
+}
+
+#[test]
+fn test_parse_color() {
+    assert_eq!(parse_color("#1e1e1e"), Ok((30, 30, 30)));
+    assert_eq!(parse_color("FF8000"), Ok((255, 128, 0)));
+    assert_eq!(parse_color("#fff"), Ok((255, 255, 255)));
+    assert!(parse_color("#12345").is_err());
+    assert!(parse_color("#gggggg").is_err());
+}
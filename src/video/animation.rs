@@ -0,0 +1,211 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Seek},
+    sync::atomic::Ordering,
+};
+
+#[cfg(feature = "webp")]
+use image::codecs::webp::WebPDecoder;
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder},
+    AnimationDecoder, DynamicImage, ImageFormat, ImageReader, RgbaImage,
+};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::video_to_ascii::{par_select_spaced_items, AsciiVid, FrameProgress};
+use crate::{image::image_to_ascii::AsciiImg, report, timestamp, utils::utils::Verbosity, Error};
+
+/// GIF delays shorter than this (in ms) get played at [`GIF_DEFAULT_DELAY`] instead, like browsers do, since they're
+/// almost always there by mistake.
+const GIF_MIN_DELAY: f64 = 20.0;
+const GIF_DEFAULT_DELAY: f64 = 100.0;
+
+/// ## Info
+/// the frames of an animated image.
+pub struct Animation {
+    /// every frame, already composited over the ones before it (with their disposal and blending applied, and
+    /// transparent where nothing has been drawn), with the timestamp it shows up at, in seconds.
+    pub frames: Vec<(f64, RgbaImage)>,
+    /// how long a loop of the animation lasts, in seconds.
+    pub duration: f64,
+    pub format: ImageFormat,
+}
+
+/// ## Info
+/// reads the frames of an animated GIF, APNG or WebP (with the `webp` feature) without ffmpeg.
+///
+/// ## Note
+/// gives back `None` for anything else, including PNGs and WebPs with a single image; GIFs always count as
+/// animations, even with a single frame.
+pub fn read_animation<R: BufRead + Seek>(reader: R) -> Result<Option<Animation>, Error> {
+    let reader = ImageReader::new(reader).with_guessed_format()?;
+    let format = reader.format();
+    let reader = reader.into_inner();
+
+    let frames = match format {
+        Some(ImageFormat::Gif) => GifDecoder::new(reader)?.into_frames(),
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(reader)?;
+            if !decoder.is_apng()? {
+                return Ok(None);
+            }
+            decoder.apng()?.into_frames()
+        }
+        #[cfg(feature = "webp")]
+        Some(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(reader)?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            decoder.into_frames()
+        }
+        _ => return Ok(None),
+    };
+
+    let mut time = 0.0;
+    let mut animation = Animation {
+        frames: vec![],
+        duration: 0.0,
+        format: format.unwrap_or(ImageFormat::Gif),
+    };
+    for frame in frames {
+        let frame = frame?;
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        let delay = numerator as f64 / denominator.max(1) as f64;
+        let delay = match format {
+            Some(ImageFormat::Gif) if delay < GIF_MIN_DELAY => GIF_DEFAULT_DELAY,
+            _ => delay,
+        };
+
+        animation.frames.push((time, frame.into_buffer()));
+        time += delay / 1000.0;
+    }
+    animation.duration = time;
+
+    Ok(Some(animation))
+}
+
+/// ## Info
+/// like [`read_animation`], for the file at `path`.
+pub fn read_animation_file(path: &str) -> Result<Option<Animation>, Error> {
+    read_animation(BufReader::new(File::open(path)?))
+}
+
+/// ## Info
+/// whether `reader` holds an animated GIF (with more than one frame), APNG or WebP, without decoding every frame.
+pub fn is_animated<R: BufRead + Seek>(reader: R) -> bool {
+    let Ok(reader) = ImageReader::new(reader).with_guessed_format() else {
+        return false;
+    };
+    let format = reader.format();
+    let reader = reader.into_inner();
+
+    match format {
+        Some(ImageFormat::Gif) => GifDecoder::new(reader).is_ok_and(|decoder| decoder.into_frames().take(2).count() == 2),
+        Some(ImageFormat::Png) => PngDecoder::new(reader).is_ok_and(|decoder| decoder.is_apng().unwrap_or(false)),
+        #[cfg(feature = "webp")]
+        Some(ImageFormat::WebP) => WebPDecoder::new(reader).is_ok_and(|decoder| decoder.has_animation()),
+        _ => false,
+    }
+}
+
+impl AsciiVid {
+    /// ## Info
+    /// converts the frames of an animated image (see [`read_animation`]), keeping `tracker` updated like
+    /// [`AsciiVid::new_paralleled_tracked`]. The frames are converted at the same time, each with the parallel algorithm,
+    /// if `parallel`; one after the other, with the sequential one, otherwise.
+    pub fn from_animation(
+        animation: Animation,
+        n_frames: Option<usize>,
+        final_height: Option<usize>,
+        final_width: Option<usize>,
+        invert: bool,
+        grayscale: bool,
+        uniform: bool,
+        parallel: bool,
+        verbosity: Verbosity,
+        tracker: &FrameProgress,
+    ) -> Result<Self, Error> {
+        let frames = par_select_spaced_items(animation.frames, n_frames);
+        let timestamps = frames.iter().map(|(time, _)| *time).collect::<Vec<_>>();
+        tracker.start(frames.len())?;
+
+        report!(verbosity, @normal "converting {} frames of the animation...", (frames.len()));
+        let convert = |(_, frame): (f64, RgbaImage)| {
+            // a cancelled conversion skips the frames that haven't started yet.
+            if tracker.is_cancelled() {
+                return None;
+            }
+
+            let out = animation_frame(frame, final_height, final_width, invert, grayscale, uniform, parallel);
+            tracker.done.fetch_add(1, Ordering::Relaxed);
+            Some(out)
+        };
+        let images = if parallel {
+            frames.into_par_iter().map(convert).collect::<Option<Result<Vec<_>, Error>>>()
+        } else {
+            frames.into_iter().map(convert).collect::<Option<Result<Vec<_>, Error>>>()
+        };
+        let images = images.ok_or("the conversion was cancelled")??;
+        report!(verbosity, @normal "finished converting the animation");
+
+        Ok(Self(images, timestamps))
+    }
+}
+
+/// ## Info
+/// converts a single frame of an animation; there are many of them, so nothing gets reported.
+pub(crate) fn animation_frame(
+    frame: RgbaImage,
+    final_height: Option<usize>,
+    final_width: Option<usize>,
+    invert: bool,
+    grayscale: bool,
+    uniform: bool,
+    parallel: bool,
+) -> Result<AsciiImg, Error> {
+    let image = DynamicImage::ImageRgba8(frame);
+
+    if parallel {
+        AsciiImg::new_parallel(image, final_height, final_width, invert, grayscale, uniform, Verbosity::Silent)
+    } else {
+        AsciiImg::new_sequential(image, final_height, final_width, invert, grayscale, uniform, Verbosity::Silent)
+    }
+}
+
+#[test]
+fn test_read_animation() {
+    use image::{codecs::gif::GifEncoder, Delay, Frame, Rgba};
+    use std::io::Cursor;
+
+    // a red frame shown for 50ms, then twice a frame that's transparent but for a blue pixel, shown with no delay.
+    let red = Frame::from_parts(RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255])), 0, 0, Delay::from_numer_denom_ms(50, 1));
+    let mut dot = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 0]));
+    dot.put_pixel(1, 1, Rgba([0, 0, 255, 255]));
+    let dot = Frame::from_parts(dot, 0, 0, Delay::from_numer_denom_ms(0, 1));
+
+    let mut gif = vec![];
+    GifEncoder::new(&mut gif).encode_frames(vec![red, dot.clone(), dot]).unwrap();
+    assert!(is_animated(Cursor::new(&gif)));
+
+    let animation = read_animation(Cursor::new(&gif)).unwrap().unwrap();
+    let timestamps = animation.frames.iter().map(|(time, _)| (time * 1000.0).round() as u32).collect::<Vec<_>>();
+    assert_eq!(timestamps, vec![0, 50, 150]);
+    assert_eq!((animation.duration * 1000.0).round(), 250.0);
+
+    // the encoder has every frame cleared to the background once it's done, so the red doesn't show through.
+    let (_, second) = &animation.frames[1];
+    assert_eq!(second.get_pixel(1, 1), &Rgba([0, 0, 255, 255]));
+    assert_eq!(second.get_pixel(0, 0), &Rgba([0, 0, 0, 0]));
+
+    let video = AsciiVid::from_animation(animation, Some(2), Some(2), Some(2), false, false, false, false, Verbosity::Silent, &FrameProgress::default()).unwrap();
+    assert_eq!(video.1.len(), 2);
+    assert_eq!(video.1[0], 0.0);
+    assert_eq!(video.0[0].0.columns, 2);
+
+    // a still PNG isn't an animation.
+    let mut png = vec![];
+    DynamicImage::ImageRgba8(RgbaImage::new(1, 1)).write_to(&mut Cursor::new(&mut png), ImageFormat::Png).unwrap();
+    assert!(!is_animated(Cursor::new(&png)));
+    assert!(read_animation(Cursor::new(&png)).unwrap().is_none());
+}
@@ -0,0 +1,185 @@
+use clap::ValueEnum;
+
+use crate::{
+    audio::audio_to_ascii::{decode_samples, AudioOptions, SampleSink},
+    core::char::ColoredChar,
+    utils::utils::Verbosity,
+    Error,
+};
+
+use super::video_to_ascii::AsciiVid;
+
+/// ## Info
+/// what gets drawn under each frame of a video to show its audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AudioStrip {
+    /// a small waveform of the audio playing during the frame.
+    Waveform,
+    /// a single bar with the peak level of the audio playing during the frame, in dBFS.
+    Meter,
+}
+
+/// the lowest level shown by the meter; anything quieter reads as an empty bar.
+const METER_FLOOR_DB: f64 = -60.0;
+
+impl AsciiVid {
+    /// ## Info
+    /// decodes the audio track of the video at `path` and renders, for every frame, a strip as wide as the frame
+    /// showing the audio that plays from that frame's timestamp up to the next one.
+    ///
+    /// ## Args
+    /// `height`: the number of rows of the waveform strip; the meter is always a single row.
+    pub fn audio_strips(
+        &self,
+        path: &String,
+        kind: AudioStrip,
+        height: usize,
+        verbosity: Verbosity,
+    ) -> Result<Vec<String>, Error> {
+        let width = self.0.first().map(|img| img.0.columns).unwrap_or(0).max(1);
+        let timestamps = &self.1;
+
+        // the time range (in seconds) each frame is on screen for; the last frame lasts as long as the one before it.
+        let windows = timestamps
+            .iter()
+            .enumerate()
+            .map(|(index, &start)| match timestamps.get(index + 1) {
+                Some(&next) => (start, next),
+                None if index > 0 => (start, start + (start - timestamps[index - 1])),
+                None => (start, start + 1.0),
+            })
+            .collect::<Vec<_>>();
+
+        // only the peak of every column of every strip is kept while decoding.
+        let mut collector = StripPeaks {
+            windows,
+            width,
+            sample_rate: 1,
+            current: 0,
+            peaks: vec![vec![0_u16; width]; timestamps.len()],
+        };
+        decode_samples(path.into(), &AudioOptions::default(), verbosity, &mut collector)?;
+
+        let strips = collector
+            .peaks
+            .into_iter()
+            .map(|columns| match kind {
+                AudioStrip::Waveform => waveform_strip(&columns, height),
+                AudioStrip::Meter => meter_strip(columns.into_iter().max().unwrap_or(0), width),
+            })
+            .collect();
+
+        Ok(strips)
+    }
+}
+
+/// ## Info
+/// spreads the decoded samples over the frames' time ranges, keeping the peak of every strip column.
+struct StripPeaks {
+    windows: Vec<(f64, f64)>,
+    width: usize,
+    sample_rate: u32,
+    current: usize,
+    peaks: Vec<Vec<u16>>,
+}
+
+impl SampleSink for StripPeaks {
+    fn prepare(&mut self, sample_rate: u32, _expected: Option<u64>) {
+        self.sample_rate = sample_rate;
+    }
+
+    fn push(&mut self, first: u64, samples: &[i16]) {
+        for (index, sample) in samples.iter().enumerate() {
+            let time = (first + index as u64) as f64 / self.sample_rate as f64;
+
+            while self.current < self.windows.len() && time >= self.windows[self.current].1 {
+                self.current += 1;
+            }
+
+            let Some(&(from, to)) = self.windows.get(self.current) else {
+                return;
+            };
+
+            if time >= from && to > from {
+                let column = (((time - from) / (to - from)) * self.width as f64) as usize;
+                let peak = &mut self.peaks[self.current][column.min(self.width - 1)];
+                *peak = (*peak).max(sample.unsigned_abs());
+            }
+        }
+    }
+}
+
+fn amplitude(peak: u16) -> f64 {
+    (peak as f64 / i16::MAX as f64).min(1.0)
+}
+
+fn waveform_strip(peaks: &[u16], height: usize) -> String {
+    let height = height.max(1);
+    let midpoint = (height - 1) / 2;
+
+    // for every column: how many rows it reaches on each side of the midpoint, and with which character.
+    let columns = peaks
+        .iter()
+        .map(|&peak| {
+            let amplitude = amplitude(peak);
+
+            let reach = (amplitude * (midpoint + 1) as f64).ceil() as usize;
+            let ch = match ColoredChar::from_everything((amplitude * 255.0) as u8, (255, 255, 255), true, false, false).ch {
+                ' ' => ColoredChar::CHAR0_17,
+                ch => ch,
+            };
+
+            (reach, ch)
+        })
+        .collect::<Vec<_>>();
+
+    (0..height)
+        .map(|row| {
+            let offset = row.abs_diff(midpoint);
+
+            columns
+                .iter()
+                .map(|&(reach, ch)| {
+                    if offset == 0 && reach == 0 {
+                        ColoredChar::CHAR0_17
+                    } else if offset < reach {
+                        ch
+                    } else {
+                        ' '
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn meter_strip(peak: u16, width: usize) -> String {
+    let amplitude = amplitude(peak);
+    let db = if amplitude > 0.0 { (20.0 * amplitude.log10()).max(METER_FLOOR_DB) } else { METER_FLOOR_DB };
+
+    let label = if db <= METER_FLOOR_DB { " -inf dB".to_string() } else { format!(" {:5.1} dB", db) };
+    let bar_width = width.saturating_sub(label.len() + 2);
+    let filled = (((db - METER_FLOOR_DB) / -METER_FLOOR_DB) * bar_width as f64).round() as usize;
+
+    format!(
+        "[{}{}]{}",
+        ColoredChar::CHAR34_51.to_string().repeat(filled),
+        " ".repeat(bar_width - filled),
+        label
+    )
+}
+
+#[test]
+fn test_strips() {
+    let loud = i16::MAX as u16;
+
+    assert_eq!(meter_strip(loud, 30).len(), 30);
+    assert!(meter_strip(0, 30).ends_with("-inf dB"));
+    assert!(meter_strip(loud, 30).starts_with("[===="));
+
+    let strip = waveform_strip(&[loud; 10], 5);
+    assert_eq!(strip.lines().count(), 5);
+    assert!(strip.lines().all(|line| line == "@".repeat(10)));
+    assert_eq!(waveform_strip(&[0; 10], 5).lines().nth(2), Some(".".repeat(10).as_str()));
+}
@@ -1 +1,4 @@
-pub mod video_to_ascii;
\ No newline at end of file
+pub mod video_to_ascii;
+pub mod audio_strip;
+pub mod source;
+pub mod animation;
\ No newline at end of file
@@ -0,0 +1,67 @@
+use std::io::{self, Read};
+
+use tempfile::TempPath;
+
+use crate::Error;
+
+/// ## Info
+/// a video that only exists in memory (or behind a reader), copied to a temp file so ffmpeg can open it. The file gets
+/// a unique name, is only readable by the current user, and is deleted when this is dropped, whichever way the
+/// conversion ends.
+pub struct TempVideo {
+    path: String,
+    _file: TempPath,
+}
+
+impl TempVideo {
+    /// ## Info
+    /// copies `reader` to a new temp file.
+    ///
+    /// ## Args
+    /// `format`: the extension the file gets, since ffmpeg uses it to guess formats it can't sniff; `mp4` if unknown
+    /// or if it isn't a plain extension.
+    pub fn from_reader(mut reader: impl Read, format: Option<&str>) -> Result<Self, Error> {
+        let format = format
+            .filter(|format| !format.is_empty() && format.chars().all(|c| c.is_ascii_alphanumeric()))
+            .unwrap_or("mp4");
+
+        let mut file = tempfile::Builder::new()
+            .prefix("received_video_")
+            .suffix(&format!(".{}", format))
+            .tempfile()?;
+        io::copy(&mut reader, &mut file)?;
+
+        let file = file.into_temp_path();
+        let path = file.to_string_lossy().to_string();
+
+        Ok(TempVideo { path, _file: file })
+    }
+
+    /// ## Info
+    /// like [`TempVideo::from_reader`], for a video that's already in memory.
+    pub fn from_bytes(bytes: &[u8], format: Option<&str>) -> Result<Self, Error> {
+        Self::from_reader(bytes, format)
+    }
+
+    /// ## Info
+    /// where the video is, for as long as this lives.
+    pub fn path(&self) -> &String {
+        &self.path
+    }
+}
+
+#[test]
+fn test_temp_video() {
+    let video = TempVideo::from_bytes(b"not really a video", Some("webm")).unwrap();
+    let path = video.path().clone();
+
+    assert!(path.ends_with(".webm"));
+    assert_eq!(std::fs::read(&path).unwrap(), b"not really a video");
+
+    let other = TempVideo::from_bytes(b"not really a video", Some("../x")).unwrap();
+    assert!(other.path().ends_with(".mp4"));
+    assert_ne!(other.path(), &path);
+
+    drop(video);
+    assert!(!std::path::Path::new(&path).exists());
+}
@@ -1,7 +1,12 @@
-use std::path::Path;
+use std::{
+    io::Read,
+    path::Path,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
 
 use image::Rgba;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     core::{
@@ -9,10 +14,72 @@ use crate::{
         flat_matrix::FlatMatrix,
     }, image::image_to_ascii::AsciiImg, report, utils::utils::{DemureUnwrap, Verbosity}, Error
 };
+use super::{
+    animation::{animation_frame, read_animation_file},
+    source::TempVideo,
+};
 use indicatif::{ProgressBar, ProgressStyle};
 use crate::timestamp;
 
-pub struct AsciiVid(pub Vec<AsciiImg>);
+/// ## Info
+/// the ASCII frames of a video, together with the timestamp (in seconds) of each frame.
+#[derive(Serialize, Deserialize)]
+pub struct AsciiVid(pub Vec<AsciiImg>, pub Vec<f64>);
+
+/// ## Info
+/// how a stream of frames from [`AsciiVid::stream_paralleled`] or [`AsciiVid::stream_sequential`] ended, with the
+/// number of frames handed over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamOutcome {
+    /// every frame was converted and handed over.
+    Finished(usize),
+    /// the receiver of the frames stopped taking them before the end.
+    Cancelled(usize),
+}
+
+/// ## Info
+/// counts the frames converted so far by [`AsciiVid::new_paralleled_tracked`] or [`AsciiVid::new_sequential_tracked`],
+/// and lets another thread cancel the conversion.
+///
+/// ## Note
+/// `total` stays at 0 until the frames have been decoded.
+#[derive(Debug, Default)]
+pub struct FrameProgress {
+    pub(crate) done: AtomicUsize,
+    total: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl FrameProgress {
+    pub fn done(&self) -> usize {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// ## Info
+    /// makes the conversion stop at the next frame and return an error.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn start(&self, total: usize) -> Result<(), Error> {
+        self.done.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+
+        if self.is_cancelled() {
+            Err("the conversion was cancelled".into())
+        } else {
+            Ok(())
+        }
+    }
+}
 
 impl AsciiVid {
     pub fn new_paralleled(
@@ -25,8 +92,30 @@ impl AsciiVid {
         uniform: bool,
         verbosity: Verbosity,
     ) -> Result<Self, Error> {
+        Self::new_paralleled_tracked(path, n_frames, final_height, final_width, invert, grayscale, uniform, verbosity, &FrameProgress::default())
+    }
+
+    /// ## Info
+    /// like [`AsciiVid::new_paralleled`], but keeps `tracker` updated with the number of converted frames, and stops early
+    /// if it gets cancelled.
+    pub fn new_paralleled_tracked(
+        path: &String,
+        n_frames: Option<usize>,
+        final_height: Option<usize>,
+        final_width: Option<usize>,
+        invert: bool,
+        grayscale: bool,
+        uniform: bool,
+        verbosity: Verbosity,
+        tracker: &FrameProgress,
+    ) -> Result<Self, Error> {
+        // animated images don't need ffmpeg.
+        if let Some(animation) = read_animation_file(path)? {
+            return Self::from_animation(animation, n_frames, final_height, final_width, invert, grayscale, uniform, true, verbosity, tracker);
+        }
+
         report!(verbosity, @verbose "starting ffmpeg...");
-        video_rs::init().unwrap();
+        video_rs::init().map_err(|_| Error::InternalError("ffmpeg couldn't start"))?;
         report!(verbosity, @verbose "ffmpeg started");
 
         let mut decoder = video_rs::Decoder::new(Path::new(&path))?;
@@ -40,6 +129,8 @@ impl AsciiVid {
                 .collect::<Vec<_>>(),
             n_frames.and_then(|x| Some(x as usize)),
         );
+        let timestamps = images.iter().map(|(time, _)| time.as_secs_f64()).collect::<Vec<_>>();
+        tracker.start(images.len())?;
         report!(verbosity, @verbose "finished getting frames");
 
         let show_progress = {
@@ -66,6 +157,11 @@ impl AsciiVid {
         let ascii_images = images
             .into_par_iter()
             .map(|frame| {
+                // a cancelled conversion skips the frames that haven't started yet.
+                if tracker.is_cancelled() {
+                    return None;
+                }
+
                 let frame = frame.1;
                 let x = (0..frame.shape()[0])
                     .into_par_iter()
@@ -111,15 +207,18 @@ impl AsciiVid {
                     });
                     prog.inc(1);
                 }
-                out
+                tracker.done.fetch_add(1, Ordering::Relaxed);
+                Some(out)
             })
-            .collect::<Vec<_>>();
+            .collect::<Option<Vec<_>>>();
         report!(verbosity, @normal "finished general conversion algorithm");
 
         if let Some(prog) = progress {prog.finish()};
+        let ascii_images = ascii_images.ok_or("the conversion was cancelled")?;
 
         Ok(Self(
             ascii_images.into_iter().map(|x| AsciiImg(x)).collect(),
+            timestamps,
         ))
     }
 
@@ -133,8 +232,29 @@ impl AsciiVid {
         uniform: bool,
         verbosity: Verbosity,
     ) -> Result<Self, Error> {
+        Self::new_sequential_tracked(path, n_frames, final_height, final_width, invert, grayscale, uniform, verbosity, &FrameProgress::default())
+    }
+
+    /// ## Info
+    /// like [`AsciiVid::new_sequential`], but keeps `tracker` updated with the number of converted frames, and stops early
+    /// if it gets cancelled.
+    pub fn new_sequential_tracked(
+        path: &String,
+        n_frames: Option<usize>,
+        final_height: Option<usize>,
+        final_width: Option<usize>,
+        invert: bool,
+        grayscale: bool,
+        uniform: bool,
+        verbosity: Verbosity,
+        tracker: &FrameProgress,
+    ) -> Result<Self, Error> {
+        if let Some(animation) = read_animation_file(path)? {
+            return Self::from_animation(animation, n_frames, final_height, final_width, invert, grayscale, uniform, false, verbosity, tracker);
+        }
+
         report!(verbosity, @verbose "starting ffmpeg...");
-        video_rs::init().unwrap();
+        video_rs::init().map_err(|_| Error::InternalError("ffmpeg couldn't start"))?;
         report!(verbosity, @verbose "ffmpeg started");
         
         let mut decoder = video_rs::Decoder::new(Path::new(&path))?;
@@ -148,6 +268,8 @@ impl AsciiVid {
                 .collect::<Vec<_>>(),
             n_frames.and_then(|x| Some(x as usize)),
         );
+        let timestamps = images.iter().map(|(time, _)| time.as_secs_f64()).collect::<Vec<_>>();
+        tracker.start(images.len())?;
         report!(verbosity, @verbose "finished getting frames");
 
         let show_progress = {
@@ -174,6 +296,11 @@ impl AsciiVid {
         let ascii_images = images
             .into_iter()
             .map(|frame| {
+                // a cancelled conversion skips the frames that haven't started yet.
+                if tracker.is_cancelled() {
+                    return None;
+                }
+
                 let frame = frame.1;
                 let x = (0..frame.shape()[0])
                     .into_iter()
@@ -221,19 +348,89 @@ impl AsciiVid {
                 });
                     prog.inc(1);
                 }
-                out
+                tracker.done.fetch_add(1, Ordering::Relaxed);
+                Some(out)
             })
-               .collect::<Vec<_>>();
+               .collect::<Option<Vec<_>>>();
         report!(verbosity, @normal "finished general conversion algorithm");
 
 
         if let Some(prog) = progress {prog.finish()};
+        let ascii_images = ascii_images.ok_or("the conversion was cancelled")?;
 
         Ok(Self(
             ascii_images.into_iter().map(|x| AsciiImg(x)).collect(),
+            timestamps,
         ))
     }
 
+    /// ## Info
+    /// converts a video that isn't on disk, eg: an upload held in memory, with the parallel algorithm if `parallel`,
+    /// keeping `tracker` updated like [`AsciiVid::new_paralleled_tracked`]. ffmpeg only decodes from a path, so the
+    /// video goes through a [`TempVideo`], which is deleted before this returns, even on errors.
+    ///
+    /// ## Args
+    /// `format`: the extension of the video (eg: `webm`), for the formats ffmpeg can't recognize by their content.
+    pub fn from_reader(
+        reader: impl Read,
+        format: Option<&str>,
+        n_frames: Option<usize>,
+        final_height: Option<usize>,
+        final_width: Option<usize>,
+        invert: bool,
+        grayscale: bool,
+        uniform: bool,
+        parallel: bool,
+        verbosity: Verbosity,
+        tracker: &FrameProgress,
+    ) -> Result<Self, Error> {
+        let video = TempVideo::from_reader(reader, format)?;
+
+        if parallel {
+            Self::new_paralleled_tracked(video.path(), n_frames, final_height, final_width, invert, grayscale, uniform, verbosity, tracker)
+        } else {
+            Self::new_sequential_tracked(video.path(), n_frames, final_height, final_width, invert, grayscale, uniform, verbosity, tracker)
+        }
+    }
+
+    /// ## Info
+    /// converts the video at `path` one frame at a time, handing every ASCII frame to `on_frame` (with its index and
+    /// timestamp in seconds) as soon as it's ready, instead of collecting them all first. Every frame is converted with
+    /// the parallel algorithm. Returns how the stream ended, with the number of frames `on_frame` took.
+    ///
+    /// ## Note
+    /// the conversion stops early, without an error but as [`StreamOutcome::Cancelled`], if `on_frame` returns `false`. `n_frames` relies on the frame
+    /// count in the video's header; if there's none, every frame is kept.
+    pub fn stream_paralleled(
+        path: &String,
+        n_frames: Option<usize>,
+        final_height: Option<usize>,
+        final_width: Option<usize>,
+        invert: bool,
+        grayscale: bool,
+        uniform: bool,
+        verbosity: Verbosity,
+        on_frame: impl FnMut(usize, f64, AsciiImg) -> bool,
+    ) -> Result<StreamOutcome, Error> {
+        stream_frames(path, n_frames, final_height, final_width, invert, grayscale, uniform, true, verbosity, on_frame)
+    }
+
+    /// ## Info
+    /// like [`AsciiVid::stream_paralleled`], but every frame is converted with the sequential algorithm.
+    pub fn stream_sequential(
+        path: &String,
+        n_frames: Option<usize>,
+        final_height: Option<usize>,
+        final_width: Option<usize>,
+        invert: bool,
+        grayscale: bool,
+        uniform: bool,
+        verbosity: Verbosity,
+        on_frame: impl FnMut(usize, f64, AsciiImg) -> bool,
+    ) -> Result<StreamOutcome, Error> {
+        stream_frames(path, n_frames, final_height, final_width, invert, grayscale, uniform, false, verbosity, on_frame)
+    }
+
     // pub fn new_parallel_file(
     //     path: String,
     //     target_height: Option<usize>,
@@ -269,7 +466,94 @@ impl AsciiVid {
     // }
 }
 
-fn par_select_spaced_items<I>(iter: Vec<I>, n_frames_to_keep: Option<usize>) -> Vec<I>
+fn stream_frames(
+    path: &String,
+    n_frames: Option<usize>,
+    final_height: Option<usize>,
+    final_width: Option<usize>,
+    invert: bool,
+    grayscale: bool,
+    uniform: bool,
+    parallel: bool,
+    verbosity: Verbosity,
+    mut on_frame: impl FnMut(usize, f64, AsciiImg) -> bool,
+) -> Result<StreamOutcome, Error> {
+    if let Some(animation) = read_animation_file(path)? {
+        let frames = par_select_spaced_items(animation.frames, n_frames);
+        let total = frames.len();
+
+        for (index, (time, frame)) in frames.into_iter().enumerate() {
+            let out = animation_frame(frame, final_height, final_width, invert, grayscale, uniform, parallel)?;
+            if !on_frame(index, time, out) {
+                report!(verbosity, @normal "the receiver of the frames went away, stopping the conversion");
+                return Ok(StreamOutcome::Cancelled(index));
+            }
+        }
+
+        return Ok(StreamOutcome::Finished(total));
+    }
+
+    report!(verbosity, @verbose "starting ffmpeg...");
+    video_rs::init().map_err(|_| Error::InternalError("ffmpeg couldn't start"))?;
+    report!(verbosity, @verbose "ffmpeg started");
+
+    let mut decoder = video_rs::Decoder::new(Path::new(&path))?;
+
+    // the same evenly spaced frames `select_spaced_items` would pick, worked out ahead of time from the header.
+    let ratio = match (n_frames, decoder.frames()) {
+        (Some(n_frames), Ok(total)) if n_frames > 0 && (n_frames as u64) < total => total as f64 / n_frames as f64,
+        _ => 1.0,
+    };
+    let keep_limit = n_frames.unwrap_or(usize::MAX);
+
+    let mut sent = 0;
+    for (index, decoded) in decoder.decode_iter().enumerate() {
+        // the decoder says it's done with an error; any other error is a broken video, not its end.
+        let (time, frame) = match decoded {
+            Ok(decoded) => decoded,
+            Err(video_rs::Error::ReadExhausted | video_rs::Error::DecodeExhausted) => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        if sent >= keep_limit {
+            break;
+        }
+        if index != (sent as f64 * ratio).floor() as usize {
+            continue;
+        }
+
+        let pixels = (0..frame.shape()[0])
+            .map(|px_outer| {
+                (0..frame.shape()[1])
+                    .map(|px_inner| {
+                        let x = frame.slice(ndarray::s![px_outer, px_inner, ..]).to_slice().unwrap();
+                        Rgba::<u8>::from([x[0], x[1], x[2], 255])
+                    })
+                    .collect::<Vec<Rgba<u8>>>()
+            })
+            .collect::<FlatMatrix<Rgba<u8>>>();
+
+        let (final_width, final_height) = (final_width, final_height)
+            .demure_unwrap(frame.shape()[1], frame.shape()[0]);
+
+        let out = if parallel {
+            algo_parallel(pixels, final_height, final_width, grayscale, invert, uniform, verbosity, false)
+        } else {
+            algo_sequential(pixels, final_height, final_width, grayscale, invert, uniform, verbosity, false)
+        };
+        report!(verbosity, @verbose "streamed frame {}", sent);
+
+        if !on_frame(sent, time.as_secs_f64(), AsciiImg(out)) {
+            report!(verbosity, @normal "the receiver of the frames went away, stopping the conversion");
+            return Ok(StreamOutcome::Cancelled(sent));
+        }
+        sent += 1;
+    }
+
+    Ok(StreamOutcome::Finished(sent))
+}
+
+pub(crate) fn par_select_spaced_items<I>(iter: Vec<I>, n_frames_to_keep: Option<usize>) -> Vec<I>
 where
     I: Send + Sync + Clone,
 {